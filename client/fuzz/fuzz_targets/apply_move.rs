@@ -0,0 +1,32 @@
+#![no_main]
+
+use client_fuzz::{check_gamestate, check_invariants, decode_game};
+use libfuzzer_sys::fuzz_target;
+
+// Plays arbitrary moves, see `decode_game`. Gamestate::try_apply_move has to reject the
+// illegal ones without changing anything. Board::apply_move doesn't check legality, but
+// has to keep the board consistent for any move of a piece onto a square without a
+// piece of its team.
+fuzz_target!(|data: &[u8]| {
+    let (mut state, moves) = decode_game(data);
+    check_gamestate(&state);
+    let mut board = state.board;
+
+    for mov in moves {
+        let before = state;
+        match state.try_apply_move(&mov) {
+            Ok(()) => check_gamestate(&state),
+            Err(_) => assert_eq!(state, before, "Rejected {:?} changed the gamestate", mov),
+        }
+
+        // Either team may move, in any order and as far as it likes
+        let team = match board.piece_at(mov.from) {
+            Some(piece) if piece.piece_type() == mov.piece => piece.team(),
+            _ => continue,
+        };
+        if !board.player_pieces(team).get_bit(mov.to) {
+            board.apply_move(&mov, team);
+            check_invariants(&board);
+        }
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use client::game::{Fen, Gamestate};
+use client_fuzz::check_gamestate;
+use libfuzzer_sys::fuzz_target;
+
+// Any text either fails to parse or yields a valid gamestate, which survives a round trip
+fuzz_target!(|fen: &str| {
+    if let Ok(state) = Gamestate::load_fen(fen) {
+        check_gamestate(&state);
+        let written = state.to_fen();
+        let reloaded = Gamestate::load_fen(&written).expect("Written FEN doesn't load");
+        assert_eq!(reloaded, state, "Round trip of {} changed it", written);
+    }
+});
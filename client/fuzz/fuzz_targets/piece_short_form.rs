@@ -0,0 +1,10 @@
+#![no_main]
+
+use client::game::{Piece, ShortForm};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|short_form: String| {
+    if let Ok(piece) = Piece::from_short_form(&short_form) {
+        assert_eq!(piece.to_short_form(), short_form);
+    }
+});
@@ -0,0 +1,60 @@
+#![no_main]
+
+use std::collections::HashMap;
+
+use arbitrary::Arbitrary;
+use client::game::Gamestate;
+use client_fuzz::check_gamestate;
+use libfuzzer_sys::fuzz_target;
+use socha_client_2022::game::{
+    Board as SCBoard, Piece as SCPiece, PieceType as SCPieceType, State as SCState, Team as SCTeam,
+    Vec2,
+};
+
+/// A state as the server might send it, with pieces anywhere, including outside of the
+/// board, and any counts, turns and ambers.
+#[derive(Debug, Arbitrary)]
+struct Input {
+    pieces: Vec<(i32, i32, u8, bool, usize)>,
+    turn: usize,
+    ambers_one: Option<usize>,
+    ambers_two: Option<usize>,
+}
+
+fn piece_type(byte: u8) -> SCPieceType {
+    match byte % 4 {
+        0 => SCPieceType::Herzmuschel,
+        1 => SCPieceType::Moewe,
+        2 => SCPieceType::Seestern,
+        _ => SCPieceType::Robbe,
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let pieces: HashMap<Vec2, SCPiece> = input
+        .pieces
+        .into_iter()
+        .map(|(x, y, piece_type_byte, one, count)| {
+            let team = if one { SCTeam::One } else { SCTeam::Two };
+            let piece = SCPiece::new(piece_type(piece_type_byte), team, count);
+            (Vec2::new(x, y), piece)
+        })
+        .collect();
+    let mut ambers = HashMap::new();
+    if let Some(ambers_one) = input.ambers_one {
+        ambers.insert(SCTeam::One, ambers_one);
+    }
+    if let Some(ambers_two) = input.ambers_two {
+        ambers.insert(SCTeam::Two, ambers_two);
+    }
+    let state = SCState::new(input.turn, SCTeam::One, SCBoard::new(pieces), None, ambers);
+
+    // States beyond the turns and ambers of a gamestate have to be rejected
+    let gamestate = match Gamestate::try_from(state) {
+        Ok(gamestate) => gamestate,
+        Err(_) => return,
+    };
+    check_gamestate(&gamestate);
+    let back = Gamestate::try_from(SCState::from(gamestate)).unwrap();
+    assert_eq!(back, gamestate);
+});
@@ -0,0 +1,93 @@
+//! Writes the seed corpus of the fuzz targets from positions of self-play games.
+//!
+//! Usage: `cargo run --release --bin seed_corpus [games]`, run in `client/fuzz`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use client::algorithms::heuristics::EVAL_2603_1;
+use client::algorithms::{Algorithm, MinMax};
+use client::game::{Fen, Gamestate, IGamestate, Move, ShortForm, Tile};
+use client_fuzz::encode_game;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Every how many plies a position of a game is written
+const SAMPLE_EVERY: usize = 8;
+
+fn write(target: &str, name: &str, data: &[u8]) {
+    let dir = Path::new("corpus").join(target);
+    fs::create_dir_all(&dir).expect("Can't create the corpus directory");
+    fs::write(dir.join(format!("selfplay-{}", name)), data).expect("Can't write a seed");
+}
+
+/// Plays a game of a shallow search against itself, with some random moves for variety.
+/// Returns: the starting position and the moves of the game
+fn self_play(rng: &mut StdRng) -> (Gamestate, Vec<Move>) {
+    let start = Gamestate::new_random(rng);
+    let mut engine = MinMax::new(2, EVAL_2603_1);
+    let mut state = start;
+    let mut moves = vec![];
+    while !state.game_over() {
+        let available = state.available_moves_current_player();
+        if available.is_empty() {
+            break;
+        }
+        let mov = if rng.gen_bool(0.2) {
+            available[rng.gen_range(0..available.len())]
+        } else {
+            engine.best_move(state, state.current_player())
+        };
+        state.apply_move(&mov);
+        moves.push(mov);
+    }
+    (start, moves)
+}
+
+fn main() {
+    let games: u64 = match std::env::args().nth(1) {
+        Some(games) => games
+            .parse()
+            .expect("The number of games has to be a number"),
+        None => 8,
+    };
+    let mut short_forms = BTreeSet::new();
+    for game in 0..games {
+        let mut rng = StdRng::seed_from_u64(game);
+        let (start, moves) = self_play(&mut rng);
+
+        let mut states = vec![start];
+        for mov in &moves {
+            let mut state = *states.last().unwrap();
+            state.apply_move(mov);
+            states.push(state);
+        }
+
+        for (ply, state) in states.iter().enumerate().step_by(SAMPLE_EVERY) {
+            let name = format!("{}-{}", game, ply);
+            write("load_fen", &name, state.to_fen().as_bytes());
+            // The rest of the game, followed by a few random and mostly illegal moves
+            let mut data = encode_game(state, &moves[ply..]);
+            data.extend((0..12).map(|_| rng.gen::<u8>()));
+            write("apply_move", &name, &data);
+        }
+        let end = states.last().unwrap();
+        write(
+            "load_fen",
+            &format!("{}-end", game),
+            end.to_fen().as_bytes(),
+        );
+        for state in &states {
+            for tile in state.board.iter_tiles() {
+                if let Tile::Piece(piece) = tile {
+                    short_forms.insert(piece.to_short_form());
+                }
+            }
+        }
+        println!("Game {}: {} plies", game, moves.len());
+    }
+    for (i, short_form) in short_forms.iter().enumerate() {
+        write("piece_short_form", &i.to_string(), short_form.as_bytes());
+    }
+}
@@ -0,0 +1,76 @@
+//! Helpers shared by the fuzz targets and the generator of their seed corpus.
+
+use client::game::{Board, Fen, Gamestate, Move, PieceType};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Checks the invariants every board has to hold, whatever moves were applied to it,
+/// even in release builds, in which [`Board`] skips its own checks.
+/// Panics, naming all violated invariants and the board.
+pub fn check_invariants(board: &Board) {
+    if let Err(violations) = board.validate() {
+        let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        panic!("{} on {:?}", violations.join("; "), board);
+    }
+}
+
+/// Checks the invariants of the board and that the incrementally updated hash is right.
+pub fn check_gamestate(state: &Gamestate) {
+    check_invariants(&state.board);
+    let mut recalculated = *state;
+    recalculated.recalculate_hash();
+    assert_eq!(
+        recalculated.hash,
+        state.hash,
+        "Wrong hash for {}",
+        state.to_fen()
+    );
+}
+
+/// Input of the `apply_move` target: a FEN, a newline and three bytes per move for the
+/// start, the target and the type of the piece. Bytes out of range wrap around, so any
+/// input decodes to moves, most of them illegal. If the FEN doesn't load, the game
+/// starts from a random position seeded by it instead.
+pub fn decode_game(data: &[u8]) -> (Gamestate, Vec<Move>) {
+    let (fen, moves) = match data.iter().position(|&b| b == b'\n') {
+        Some(newline) => (&data[..newline], &data[newline + 1..]),
+        None => (data, &[][..]),
+    };
+    let state = std::str::from_utf8(fen)
+        .ok()
+        .and_then(|fen| Gamestate::load_fen(fen).ok())
+        .unwrap_or_else(|| {
+            let seed = fen
+                .iter()
+                .fold(0u64, |seed, &b| seed.rotate_left(8) ^ b as u64);
+            Gamestate::new_random(&mut StdRng::seed_from_u64(seed))
+        });
+    let moves = moves
+        .chunks_exact(3)
+        .map(|chunk| Move {
+            from: chunk[0] % 64,
+            to: chunk[1] % 64,
+            piece: piece_type(chunk[2]),
+        })
+        .collect();
+    (state, moves)
+}
+
+/// The inverse of [`decode_game`].
+pub fn encode_game(state: &Gamestate, moves: &[Move]) -> Vec<u8> {
+    let mut data = state.to_fen().into_bytes();
+    data.push(b'\n');
+    for mov in moves {
+        data.extend([mov.from, mov.to, mov.piece as u8]);
+    }
+    data
+}
+
+pub fn piece_type(byte: u8) -> PieceType {
+    match byte % 4 {
+        0 => PieceType::Herzmuschel,
+        1 => PieceType::Moewe,
+        2 => PieceType::Seestern,
+        _ => PieceType::Robbe,
+    }
+}
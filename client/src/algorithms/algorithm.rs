@@ -1,7 +1,73 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
 use crate::game::{Gamestate, Move, Team};
 
 pub trait Algorithm: Clone {
     fn best_move(&mut self, state: Gamestate, my_team: Team) -> Move;
+
+    /// Sets the time the next searches should take, algorithms without time control ignore it
+    fn set_time_budget(&mut self, _budget: Duration) {}
+
+    /// Lets the algorithm publish the best move found so far during a search, so it can
+    /// be used if the search doesn't finish in time, and learn that it was cancelled
+    fn report_progress_to(&mut self, _best_so_far: BestSoFar) {}
+
+    /// Information about the last search, e.g. the evaluation of the chosen move
+    fn search_info(&self) -> SearchInfo {
+        SearchInfo::default()
+    }
+
+    /// Short description of the algorithm and its settings, e.g. for game records
+    fn name(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+}
+
+/// Results of the last search of an [`Algorithm`], besides the move itself.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SearchInfo {
+    /// Evaluation of the chosen move from the perspective of the searching team
+    pub score: Option<i32>,
+    /// Deepest search of a root move
+    pub depth: Option<u8>,
+    /// Number of visited positions
+    pub nodes: Option<u64>,
+    pub search_time: Option<Duration>,
+}
+
+/// The best move of a running search, shared between the search and whoever waits for it.
+/// Whoever waits can also cancel the search, e.g. once it missed its deadline.
+#[derive(Debug, Clone, Default)]
+pub struct BestSoFar {
+    best: Arc<Mutex<Option<Move>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl BestSoFar {
+    pub fn set(&self, mov: Move) {
+        if let Ok(mut best) = self.best.lock() {
+            *best = Some(mov);
+        }
+    }
+
+    /// Returns: the best move so far, which is still available if the search panicked
+    pub fn get(&self) -> Option<Move> {
+        match self.best.lock() {
+            Ok(best) => *best,
+            Err(poisoned) => *poisoned.into_inner(),
+        }
+    }
+
+    /// Asks the search to stop as soon as possible, its result won't be used.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
 }
 
 pub type EvaluationFunction<G, E> = fn(&G, Team) -> E;
@@ -69,3 +69,36 @@ pub static EVAL_2603_1: EvaluationFunction<Gamestate, i32> = |state, team| -> i3
     }
     eval
 };
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithms::registry::{find_evaluation, EVALUATIONS};
+    use crate::game::{Gamestate, IGamestate, Team};
+    use rand::prelude::SliceRandom;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_symmetry() {
+        let mut rng = StdRng::seed_from_u64(49);
+        for (name, _) in EVALUATIONS {
+            let evaluation = find_evaluation(name).unwrap();
+            for _ in 0..20 {
+                let mut state = Gamestate::new_random(&mut rng);
+                while !state.game_over() {
+                    for team in [Team::ONE, Team::TWO] {
+                        let eval = evaluation(&state, team);
+                        let flipped = evaluation(&state.flipped(), team.opponent());
+                        let mirrored = evaluation(&state.mirrored(), team);
+                        assert_eq!(flipped, eval, "{} of flipped {}", name, state);
+                        assert_eq!(mirrored, eval, "{} of mirrored {}", name, state);
+                    }
+                    match state.available_moves_current_player().choose(&mut rng) {
+                        Some(m) => state.apply_move(m),
+                        None => break,
+                    }
+                }
+            }
+        }
+    }
+}
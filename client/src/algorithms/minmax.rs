@@ -1,7 +1,9 @@
-use crate::algorithms::{Algorithm, EvaluationFunction};
+use crate::algorithms::{Algorithm, BestSoFar, EvaluationFunction, SearchInfo};
 use crate::for_each_move;
 use crate::game::{Gamestate, IGamestate, Move, Team};
+use log::{debug, trace};
 use num_traits::{Bounded, Num, NumCast};
+use std::cell::Cell;
 use std::fmt::Display;
 use std::time::{Duration, Instant};
 
@@ -10,6 +12,15 @@ pub struct MinMax<E: MinMaxState + IGamestate> {
     max_depth: u8,
     my_team: Team,
     evaluation: fn(&E, Team) -> E::EvalType,
+    last_value: Option<E::EvalType>,
+    last_depth: Option<u8>,
+    last_time: Option<Duration>,
+    /// Positions visited by the current search
+    nodes: Cell<u64>,
+    time_budget: Duration,
+    /// Upper limit for the time budget
+    max_time: Option<Duration>,
+    best_so_far: Option<BestSoFar>,
 }
 
 pub trait MinMaxState {
@@ -25,9 +36,28 @@ impl MinMax<Gamestate> {
             max_depth: search_depth,
             my_team: Team::ONE, //Gets corrected anyway.
             evaluation,
+            last_value: None,
+            last_depth: None,
+            last_time: None,
+            nodes: Cell::new(0),
+            time_budget: Duration::from_millis(1800),
+            max_time: None,
+            best_so_far: None,
         }
     }
 
+    /// Limits the time of each search, even if the time management allows more.
+    pub fn with_max_time(mut self, max_time: Duration) -> Self {
+        self.time_budget = self.time_budget.min(max_time);
+        self.max_time = Some(max_time);
+        self
+    }
+
+    /// Returns: whether the search was cancelled, e.g. by the watchdog
+    fn cancelled(&self) -> bool {
+        matches!(&self.best_so_far, Some(best_so_far) if best_so_far.is_cancelled())
+    }
+
     fn recommend_move(
         &mut self,
         state: Gamestate,
@@ -35,29 +65,36 @@ impl MinMax<Gamestate> {
     ) -> <Gamestate as IGamestate>::MoveType {
         let mut move_value_pairs = vec![];
         self.my_team = my_team;
+        self.nodes.set(0);
+        let search_start = Instant::now();
+        let mut deepest = 0;
 
         let mut move_index: u8 = 0;
         let move_count = state.count_moves(my_team);
         let mut dynamic_depth: u8 = self.max_depth; // actually start_depth
-        let max_search_duration = Duration::from_millis(1800);
+        let max_search_duration = self.time_budget;
         let mut average_search_duration = Duration::from_millis(0);
         let mut last_duration = Duration::from_millis(0);
 
-        println!("Move count: {}", move_count);
+        debug!("Move count: {}", move_count);
         state.for_each_move(self.my_team, &mut |mov| {
+            // At least one move is needed to return, which is searched quickly after all
+            if self.cancelled() && !move_value_pairs.is_empty() {
+                return;
+            }
             let start_timer = Instant::now();
             move_index += 1;
 
             if move_index > 2 {
                 let end_average_millis = average_search_duration.as_millis() * move_count as u128;
                 let end_last_millis = last_duration.as_millis() * move_count as u128;
-                println!("Estimated AVERAGE end duration: {} ms", end_average_millis);
-                println!("Estimated LAST end duration: {} ms", end_last_millis);
+                trace!("Estimated AVERAGE end duration: {} ms", end_average_millis);
+                trace!("Estimated LAST end duration: {} ms", end_last_millis);
 
                 if end_average_millis >= max_search_duration.as_millis()
                     || end_last_millis > max_search_duration.as_millis()
                 {
-                    dynamic_depth -= 1;
+                    dynamic_depth = dynamic_depth.saturating_sub(1).max(1);
                 } else if (end_average_millis * move_count as u128)
                     < max_search_duration.as_millis()
                     && (end_last_millis * move_count as u128) < max_search_duration.as_millis()
@@ -66,11 +103,12 @@ impl MinMax<Gamestate> {
                 }
             }
 
-            println!(
+            trace!(
                 "Average search duration: {} ms",
                 average_search_duration.as_millis()
             );
-            println!("Dynamic depth: {}", dynamic_depth);
+            trace!("Dynamic depth: {}", dynamic_depth);
+            deepest = deepest.max(dynamic_depth);
 
             let mut child = state.clone();
             child.apply_move(&mov);
@@ -89,10 +127,18 @@ impl MinMax<Gamestate> {
                     + last_duration.as_millis())
                     / move_index as u128) as u64,
             );
+            if let Some(best_so_far) = &self.best_so_far {
+                if move_value_pairs.iter().all(|(best, _)| value > *best) {
+                    best_so_far.set(mov);
+                }
+            }
             move_value_pairs.push((value, mov));
         });
         let max = move_value_pairs.iter().max_by_key(|pair| pair.0);
-        println!("Value: {}", max.unwrap().0);
+        debug!("Value: {}", max.unwrap().0);
+        self.last_value = Some(max.unwrap().0);
+        self.last_depth = Some(deepest);
+        self.last_time = Some(search_start.elapsed());
         max.unwrap().1.clone()
     }
 
@@ -104,7 +150,8 @@ impl MinMax<Gamestate> {
         mut alpha: <Gamestate as MinMaxState>::EvalType,
         mut beta: <Gamestate as MinMaxState>::EvalType,
     ) -> <Gamestate as MinMaxState>::EvalType {
-        if depth == 0 || state.game_over() {
+        self.nodes.set(self.nodes.get() + 1);
+        if depth == 0 || state.game_over() || self.cancelled() {
             return (self.evaluation)(&state, self.my_team);
         }
 
@@ -150,8 +197,55 @@ impl Algorithm for MinMax<Gamestate> {
     fn best_move(&mut self, state: Gamestate, my_team: Team) -> Move {
         self.recommend_move(state, my_team)
     }
+
+    fn search_info(&self) -> SearchInfo {
+        SearchInfo {
+            score: self.last_value,
+            depth: self.last_depth,
+            nodes: Some(self.nodes.get()),
+            search_time: self.last_time,
+        }
+    }
+
+    fn set_time_budget(&mut self, budget: Duration) {
+        self.time_budget = match self.max_time {
+            Some(max_time) => budget.min(max_time),
+            None => budget,
+        };
+    }
+
+    fn report_progress_to(&mut self, best_so_far: BestSoFar) {
+        self.best_so_far = Some(best_so_far);
+    }
+
+    fn name(&self) -> String {
+        format!("MinMax(depth {})", self.max_depth)
+    }
 }
 
 impl MinMaxState for Gamestate {
     type EvalType = i32;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::heuristics::EVAL_2603_1;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_cancel() {
+        let state = Gamestate::new_random(&mut StdRng::seed_from_u64(4));
+        let best_so_far = BestSoFar::default();
+        best_so_far.cancel();
+        let mut search = MinMax::new(12, EVAL_2603_1);
+        search.report_progress_to(best_so_far);
+
+        let started = Instant::now();
+        let mov = search.best_move(state, state.current_player());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(state.is_legal(&mov));
+        assert_eq!(search.search_info().nodes, Some(1));
+    }
+}
@@ -4,6 +4,7 @@ mod minmax;
 //mod parallel_minmax;
 mod random_pick;
 mod random_pick_delay;
+pub mod registry;
 
 pub use algorithm::*;
 pub use minmax::*;
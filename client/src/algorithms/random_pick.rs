@@ -14,4 +14,8 @@ impl Algorithm for RandomPick {
             .choose(&mut thread_rng())
             .unwrap()
     }
+
+    fn name(&self) -> String {
+        "RandomPick".to_string()
+    }
 }
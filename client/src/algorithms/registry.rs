@@ -0,0 +1,313 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::heuristics::EVAL_2603_1;
+use crate::algorithms::{Algorithm, BestSoFar, EvaluationFunction, MinMax, RandomPick, SearchInfo};
+use crate::game::{Gamestate, Move, Team};
+
+/// Settings of an algorithm, which can be chosen by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlgorithmOption {
+    Depth,
+    MaxTime,
+    Evaluation,
+}
+
+impl AlgorithmOption {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlgorithmOption::Depth => "depth",
+            AlgorithmOption::MaxTime => "max time",
+            AlgorithmOption::Evaluation => "evaluation",
+        }
+    }
+}
+
+/// An entry of the registry of algorithms.
+#[derive(Debug, Clone, Copy)]
+pub struct AlgorithmInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub options: &'static [AlgorithmOption],
+}
+
+pub const ALGORITHMS: &[AlgorithmInfo] = &[
+    AlgorithmInfo {
+        name: "minmax",
+        description: "Alpha-beta search, which adapts its depth to the time budget",
+        options: &[
+            AlgorithmOption::Depth,
+            AlgorithmOption::MaxTime,
+            AlgorithmOption::Evaluation,
+        ],
+    },
+    AlgorithmInfo {
+        name: "random",
+        description: "Plays a random legal move",
+        options: &[],
+    },
+];
+
+/// Names and descriptions of the evaluation functions, see [`find_evaluation`].
+pub const EVALUATIONS: &[(&str, &str)] = &[(
+    "eval-2603-1",
+    "Ambers, pieces, towers and mobility, with a bonus for winning",
+)];
+
+pub fn find_evaluation(name: &str) -> Option<EvaluationFunction<Gamestate, i32>> {
+    match name {
+        "eval-2603-1" => Some(EVAL_2603_1),
+        _ => None,
+    }
+}
+
+pub fn find_algorithm(name: &str) -> Option<&'static AlgorithmInfo> {
+    ALGORITHMS.iter().find(|info| info.name == name)
+}
+
+/// Lists the registered algorithms and evaluation functions for the command line.
+pub fn describe() -> String {
+    let mut out = String::from("Algorithms:\n");
+    for info in ALGORITHMS {
+        let options: Vec<&str> = info.options.iter().map(AlgorithmOption::name).collect();
+        out += &format!("  {:<16} {}\n", info.name, info.description);
+        if !options.is_empty() {
+            out += &format!("  {:<16} Options: {}\n", "", options.join(", "));
+        }
+    }
+    out += "Evaluations:\n";
+    for (name, description) in EVALUATIONS {
+        out += &format!("  {:<16} {}\n", name, description);
+    }
+    out
+}
+
+/// Which algorithm to play with and its settings. Settings left out use the defaults
+/// of the algorithm.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlgorithmSettings {
+    /// Name of the algorithm, see [`ALGORITHMS`]
+    pub name: Option<String>,
+    pub depth: Option<u8>,
+    pub max_time_ms: Option<u64>,
+    /// Name of the evaluation function, see [`EVALUATIONS`]
+    pub evaluation: Option<String>,
+}
+
+impl AlgorithmSettings {
+    pub const DEFAULT_ALGORITHM: &'static str = "minmax";
+    pub const DEFAULT_DEPTH: u8 = 6;
+    pub const DEFAULT_EVALUATION: &'static str = "eval-2603-1";
+
+    /// Returns: the settings, where those of `other` take precedence
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            name: other.name.or(self.name),
+            depth: other.depth.or(self.depth),
+            max_time_ms: other.max_time_ms.or(self.max_time_ms),
+            evaluation: other.evaluation.or(self.evaluation),
+        }
+    }
+
+    fn given_options(&self) -> Vec<AlgorithmOption> {
+        let given = [
+            (self.depth.is_some(), AlgorithmOption::Depth),
+            (self.max_time_ms.is_some(), AlgorithmOption::MaxTime),
+            (self.evaluation.is_some(), AlgorithmOption::Evaluation),
+        ];
+        given
+            .into_iter()
+            .filter_map(|(given, option)| given.then_some(option))
+            .collect()
+    }
+
+    /// Creates the algorithm, checking that it supports all given settings.
+    pub fn build(&self) -> Result<AnyAlgorithm, RegistryError> {
+        let name = self.name.as_deref().unwrap_or(Self::DEFAULT_ALGORITHM);
+        let info = find_algorithm(name)
+            .ok_or_else(|| RegistryError::UnknownAlgorithm(name.to_string()))?;
+        if let Some(option) = self
+            .given_options()
+            .into_iter()
+            .find(|option| !info.options.contains(option))
+        {
+            return Err(RegistryError::Unsupported {
+                algorithm: info.name,
+                option,
+            });
+        }
+
+        let depth = self.depth.unwrap_or(Self::DEFAULT_DEPTH);
+        let invalid = |option, message: &str| RegistryError::InvalidValue {
+            option,
+            message: message.to_string(),
+        };
+        if depth == 0 {
+            return Err(invalid(AlgorithmOption::Depth, "must be at least 1"));
+        }
+        let evaluation_name = self
+            .evaluation
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_EVALUATION);
+        let evaluation = find_evaluation(evaluation_name)
+            .ok_or_else(|| RegistryError::UnknownEvaluation(evaluation_name.to_string()))?;
+
+        Ok(match info.name {
+            "minmax" => {
+                let mut minmax = MinMax::new(depth, evaluation);
+                if let Some(max_time) = self.max_time_ms {
+                    minmax = minmax.with_max_time(Duration::from_millis(max_time));
+                }
+                AnyAlgorithm::MinMax(minmax)
+            }
+            _ => AnyAlgorithm::RandomPick(RandomPick),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    UnknownAlgorithm(String),
+    UnknownEvaluation(String),
+    /// The algorithm has no use for the given option.
+    Unsupported {
+        algorithm: &'static str,
+        option: AlgorithmOption,
+    },
+    InvalidValue {
+        option: AlgorithmOption,
+        message: String,
+    },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::UnknownAlgorithm(name) => {
+                write!(f, "Unknown algorithm {}, see --list", name)
+            }
+            RegistryError::UnknownEvaluation(name) => {
+                write!(f, "Unknown evaluation {}, see --list", name)
+            }
+            RegistryError::Unsupported { algorithm, option } => write!(
+                f,
+                "The algorithm {} doesn't support the option {}",
+                algorithm,
+                option.name()
+            ),
+            RegistryError::InvalidValue { option, message } => {
+                write!(f, "Invalid {}: {}", option.name(), message)
+            }
+        }
+    }
+}
+
+impl Error for RegistryError {}
+
+/// One of the registered algorithms, as [`Algorithm`] can't be used as a trait object.
+#[derive(Clone)]
+pub enum AnyAlgorithm {
+    MinMax(MinMax<Gamestate>),
+    RandomPick(RandomPick),
+}
+
+macro_rules! delegate {
+    ($self:ident, $algorithm:ident => $call:expr) => {
+        match $self {
+            AnyAlgorithm::MinMax($algorithm) => $call,
+            AnyAlgorithm::RandomPick($algorithm) => $call,
+        }
+    };
+}
+
+impl Algorithm for AnyAlgorithm {
+    fn best_move(&mut self, state: Gamestate, my_team: Team) -> Move {
+        delegate!(self, algorithm => algorithm.best_move(state, my_team))
+    }
+
+    fn set_time_budget(&mut self, budget: Duration) {
+        delegate!(self, algorithm => algorithm.set_time_budget(budget))
+    }
+
+    fn report_progress_to(&mut self, best_so_far: BestSoFar) {
+        delegate!(self, algorithm => algorithm.report_progress_to(best_so_far))
+    }
+
+    fn search_info(&self) -> SearchInfo {
+        delegate!(self, algorithm => algorithm.search_info())
+    }
+
+    fn name(&self) -> String {
+        delegate!(self, algorithm => algorithm.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(name: &str) -> AlgorithmSettings {
+        AlgorithmSettings {
+            name: Some(name.to_string()),
+            ..AlgorithmSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_build() {
+        assert!(matches!(
+            AlgorithmSettings::default().build(),
+            Ok(AnyAlgorithm::MinMax(_))
+        ));
+        for info in ALGORITHMS {
+            assert!(settings(info.name).build().is_ok(), "{}", info.name);
+        }
+        for (name, _) in EVALUATIONS {
+            assert!(find_evaluation(name).is_some());
+        }
+        assert_eq!(
+            settings("alphazero").build().err(),
+            Some(RegistryError::UnknownAlgorithm("alphazero".to_string()))
+        );
+        let random = AlgorithmSettings {
+            depth: Some(3),
+            ..settings("random")
+        };
+        assert_eq!(
+            random.build().err(),
+            Some(RegistryError::Unsupported {
+                algorithm: "random",
+                option: AlgorithmOption::Depth
+            })
+        );
+        let minmax = AlgorithmSettings {
+            evaluation: Some("none".to_string()),
+            ..settings("minmax")
+        };
+        assert_eq!(
+            minmax.build().err(),
+            Some(RegistryError::UnknownEvaluation("none".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let file = AlgorithmSettings {
+            depth: Some(4),
+            max_time_ms: Some(500),
+            ..settings("minmax")
+        };
+        let cli = AlgorithmSettings {
+            depth: Some(2),
+            ..AlgorithmSettings::default()
+        };
+        let merged = file.or(cli);
+        assert_eq!(merged.name.as_deref(), Some("minmax"));
+        assert_eq!(merged.depth, Some(2));
+        assert_eq!(merged.max_time_ms, Some(500));
+    }
+}
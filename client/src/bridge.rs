@@ -1,9 +1,40 @@
-use crate::game::{Board, Gamestate, Move, Piece, PieceType, Team};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::game::{Board, Gamestate, Move, Piece, PieceType, Team, MAX_AMBERS};
 use socha_client_2022::game::{
     Board as SCBoard, Move as SCMove, Piece as SCPiece, PieceType as SCPieceType, State as SCState,
     Team as SCTeam, Vec2,
 };
 
+/// Converts a position on our board into the server's coordinates `(x, y)`.
+/// The server's x axis runs along our ranks, so the position is `8 * x + y`.
+#[inline]
+pub fn position_to_xy(pos: u8) -> (i32, i32) {
+    ((pos / 8) as i32, (pos % 8) as i32)
+}
+
+/// Converts the server's coordinates `(x, y)` into a position on our board, see [`position_to_xy`].
+/// Returns: None, if the coordinates lie outside of the board
+#[inline]
+pub fn xy_to_position(x: i32, y: i32) -> Option<u8> {
+    if (0..8).contains(&x) && (0..8).contains(&y) {
+        Some((8 * x + y) as u8)
+    } else {
+        None
+    }
+}
+
+fn to_vec2(pos: u8) -> Vec2 {
+    let (x, y) = position_to_xy(pos);
+    Vec2::new(x, y)
+}
+
+fn from_vec2(coordinates: Vec2) -> Option<u8> {
+    xy_to_position(coordinates.x, coordinates.y)
+}
+
 impl From<SCPieceType> for PieceType {
     fn from(piece_type: SCPieceType) -> Self {
         match piece_type {
@@ -15,14 +46,38 @@ impl From<SCPieceType> for PieceType {
     }
 }
 
+impl From<PieceType> for SCPieceType {
+    fn from(piece_type: PieceType) -> Self {
+        match piece_type {
+            PieceType::Herzmuschel => SCPieceType::Herzmuschel,
+            PieceType::Moewe => SCPieceType::Moewe,
+            PieceType::Seestern => SCPieceType::Seestern,
+            PieceType::Robbe => SCPieceType::Robbe,
+        }
+    }
+}
+
 impl From<Move> for SCMove {
     fn from(m: Move) -> Self {
-        let from = Vec2::new((m.from / 8) as i32, (m.from % 8) as i32);
-        let to = Vec2::new((m.to / 8) as i32, (m.to % 8) as i32);
-        Self::new(from, to)
+        Self::new(to_vec2(m.from), to_vec2(m.to))
     }
 }
 
+/// The server's moves don't name the moving piece, so they have to be resolved against
+/// the gamestate they are played in.
+/// Returns: the move, which is not checked for legality, or None if a position lies outside
+/// of the board or there is no piece to move
+pub fn resolve_move(mov: SCMove, state: &Gamestate) -> Option<Move> {
+    let from = from_vec2(mov.from())?;
+    let to = from_vec2(mov.to())?;
+    let piece = state.board.piece_at(from)?;
+    Some(Move {
+        from,
+        to,
+        piece: piece.piece_type,
+    })
+}
+
 impl From<SCTeam> for Team {
     fn from(team: SCTeam) -> Self {
         match team {
@@ -32,6 +87,15 @@ impl From<SCTeam> for Team {
     }
 }
 
+impl From<Team> for SCTeam {
+    fn from(team: Team) -> Self {
+        match team {
+            Team::ONE => Self::One,
+            Team::TWO => Self::Two,
+        }
+    }
+}
+
 impl From<SCPiece> for Piece {
     fn from(piece: SCPiece) -> Self {
         let piece_type = PieceType::from(piece.piece_type());
@@ -45,32 +109,244 @@ impl From<SCPiece> for Piece {
     }
 }
 
+impl From<Piece> for SCPiece {
+    fn from(piece: Piece) -> Self {
+        let count = if piece.stacked { 2 } else { 1 };
+        Self::new(piece.piece_type.into(), piece.team.into(), count)
+    }
+}
+
 impl From<SCBoard> for Board {
     fn from(sc_board: SCBoard) -> Self {
         let mut board = Self::empty();
-        sc_board
-            .pieces()
-            .iter()
-            .map(|(coord, piece)| {
-                let pos = (8 * coord.x + coord.y) as u8;
-                let piece = Piece::from(*piece);
-                (pos, piece)
-            })
-            .for_each(|(pos, piece)| {
-                board.set_piece(pos, piece);
-            });
+        for (coordinates, piece) in sc_board.pieces().iter() {
+            match from_vec2(*coordinates) {
+                Some(pos) => board.set_piece(pos, Piece::from(*piece)),
+                None => log::warn!("Ignoring piece outside of the board at {:?}", coordinates),
+            }
+        }
         board
     }
 }
 
-impl From<SCState> for Gamestate {
-    fn from(state: SCState) -> Self {
+impl From<Board> for SCBoard {
+    fn from(board: Board) -> Self {
+        let pieces: HashMap<Vec2, SCPiece> = (0..64)
+            .filter_map(|pos| Some((to_vec2(pos), board.piece_at(pos)?.into())))
+            .collect();
+        Self::new(pieces)
+    }
+}
+
+/// A state of the server, which can't be held by a [`Gamestate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// The turn doesn't fit into the turn of a gamestate.
+    TurnOutOfRange(usize),
+    /// A team has more than [`MAX_AMBERS`] ambers.
+    AmbersOutOfRange { team: Team, ambers: usize },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::TurnOutOfRange(turn) => {
+                write!(
+                    f,
+                    "Turn {} is out of range, expected 0 to {}",
+                    turn,
+                    u8::MAX
+                )
+            }
+            StateError::AmbersOutOfRange { team, ambers } => write!(
+                f,
+                "Team {} has {} ambers, expected 0 to {}",
+                team, ambers, MAX_AMBERS
+            ),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+impl TryFrom<SCState> for Gamestate {
+    type Error = StateError;
+
+    fn try_from(state: SCState) -> Result<Self, Self::Error> {
         let board = Board::from(state.board().clone());
-        let turn = state.turn() as u8;
-        let ambers = [
-            (*state.ambers().get(&SCTeam::One).unwrap_or(&0)) as u8,
-            (*state.ambers().get(&SCTeam::Two).unwrap_or(&0)) as u8,
+        let turn =
+            u8::try_from(state.turn()).map_err(|_| StateError::TurnOutOfRange(state.turn()))?;
+        let mut ambers = [0; 2];
+        for team in [Team::ONE, Team::TWO] {
+            let count = *state.ambers().get(&SCTeam::from(team)).unwrap_or(&0);
+            ambers[team as usize] = u8::try_from(count)
+                .ok()
+                .filter(|count| *count <= MAX_AMBERS)
+                .ok_or(StateError::AmbersOutOfRange {
+                    team,
+                    ambers: count,
+                })?;
+        }
+        Ok(Gamestate::new_with(board, turn, ambers))
+    }
+}
+
+/// Only the board, turn and ambers survive a round trip through our gamestate. It doesn't
+/// know the last move, so the state has none, and always lets [`Team::ONE`] begin, so that
+/// is the start team of the state, whatever it was before.
+impl From<Gamestate> for SCState {
+    fn from(state: Gamestate) -> Self {
+        let ambers: HashMap<SCTeam, usize> = [Team::ONE, Team::TWO]
+            .into_iter()
+            .map(|team| (team.into(), state.ambers[team as usize] as usize))
+            .collect();
+        Self::new(
+            state.turn as usize,
+            SCTeam::One,
+            state.board.into(),
+            None,
+            ambers,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::IGamestate;
+    use rand::prelude::SliceRandom;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro128Plus;
+
+    #[test]
+    fn test_coordinates() {
+        for pos in 0..64 {
+            let (x, y) = position_to_xy(pos);
+            assert_eq!(xy_to_position(x, y), Some(pos));
+        }
+        // The server's x axis runs along our ranks
+        assert_eq!(position_to_xy(1), (0, 1));
+        assert_eq!(position_to_xy(8), (1, 0));
+        assert_eq!(xy_to_position(8, 0), None);
+        assert_eq!(xy_to_position(0, -1), None);
+    }
+
+    #[test]
+    fn test_pieces_round_trip() {
+        let piece_types = [
+            PieceType::Herzmuschel,
+            PieceType::Moewe,
+            PieceType::Seestern,
+            PieceType::Robbe,
         ];
-        Gamestate::new_with(board, turn, ambers)
+        for piece_type in piece_types {
+            assert_eq!(PieceType::from(SCPieceType::from(piece_type)), piece_type);
+            for team in [Team::ONE, Team::TWO] {
+                assert_eq!(Team::from(SCTeam::from(team)), team);
+                for stacked in [false, true] {
+                    let piece = Piece::new(piece_type, team, stacked);
+                    let sc_piece = SCPiece::from(piece);
+                    assert_eq!(sc_piece.count(), if stacked { 2 } else { 1 });
+                    assert_eq!(Piece::from(sc_piece), piece);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        for seed in 0..20 {
+            let mut rng = Xoshiro128Plus::seed_from_u64(seed);
+            let mut state = Gamestate::new_random(&mut rng);
+
+            while !state.game_over() {
+                let back = Gamestate::try_from(SCState::from(state)).unwrap();
+                assert_eq!(back.board, state.board);
+                assert_eq!(back.turn, state.turn);
+                assert_eq!(back.ambers, state.ambers);
+                assert_eq!(back.hash, state.hash);
+
+                for mov in state.available_moves_current_player().iter() {
+                    let sc_move: SCMove = (*mov).into();
+                    assert_eq!(resolve_move(sc_move, &state), Some(*mov));
+                }
+                let moves = state.available_moves_current_player();
+                state.apply_move(moves.choose(&mut rng).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_server_state_round_trip() {
+        let state = Gamestate::new_random(&mut Xoshiro128Plus::seed_from_u64(1));
+        let mov = state.available_moves_current_player()[0];
+        let mut after = state;
+        after.apply_move(&mov);
+        let sc_state = SCState::new(
+            after.turn as usize,
+            SCTeam::One,
+            after.board.into(),
+            Some(mov.into()),
+            [(SCTeam::One, 1), (SCTeam::Two, 0)].into_iter().collect(),
+        );
+
+        let back = SCState::from(Gamestate::try_from(sc_state.clone()).unwrap());
+        assert_eq!(back.turn(), sc_state.turn());
+        assert_eq!(Board::from(back.board().clone()), after.board);
+        assert_eq!(back.ambers(), sc_state.ambers());
+        assert!(back.start_team() == SCTeam::One);
+        // The last move is lost
+        assert!(back.last_move().is_none());
+    }
+
+    #[test]
+    fn test_states_out_of_range() {
+        let board = Board::new_random(&mut Xoshiro128Plus::seed_from_u64(2));
+        let sc_state = |turn, ambers_two| {
+            let ambers = [(SCTeam::One, 1), (SCTeam::Two, ambers_two)];
+            SCState::new(
+                turn,
+                SCTeam::One,
+                board.into(),
+                None,
+                ambers.into_iter().collect(),
+            )
+        };
+
+        let state = Gamestate::try_from(sc_state(255, 3)).unwrap();
+        assert_eq!(state.turn, 255);
+        assert_eq!(state.ambers, [1, 3]);
+        assert_eq!(
+            Gamestate::try_from(sc_state(256, 0)).err(),
+            Some(StateError::TurnOutOfRange(256))
+        );
+        // Not truncated to 0 ambers
+        assert_eq!(
+            Gamestate::try_from(sc_state(4, 256)).err(),
+            Some(StateError::AmbersOutOfRange {
+                team: Team::TWO,
+                ambers: 256
+            })
+        );
+        assert!(Gamestate::try_from(sc_state(4, 4)).is_err());
+    }
+
+    #[test]
+    fn test_resolve_move() {
+        let state = Gamestate::new(Board::empty());
+        let mov = SCMove::new(Vec2::new(0, 0), Vec2::new(1, 2));
+        assert_eq!(resolve_move(mov, &state), None);
+
+        let mut board = Board::empty();
+        board.set_piece(0, Piece::new(PieceType::Robbe, Team::ONE, false));
+        let state = Gamestate::new(board);
+        let expected = Move {
+            from: 0,
+            to: 10,
+            piece: PieceType::Robbe,
+        };
+        assert_eq!(resolve_move(mov, &state), Some(expected));
+        let mov = SCMove::new(Vec2::new(0, 0), Vec2::new(8, 2));
+        assert_eq!(resolve_move(mov, &state), None);
     }
 }
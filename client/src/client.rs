@@ -1,66 +1,248 @@
-use std::time::{Duration, SystemTime};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use log::info;
+use chrono::Local;
+
+use log::{debug, error, info, warn};
 use socha_client_2022::client::{DebugMode, SCClient, SCClientDelegate};
 use socha_client_2022::game::Move as SCMove;
 use socha_client_2022::game::State as SCState;
 use socha_client_2022::game::Team as SCTeam;
 use socha_client_2022::protocol::GameResult;
-use socha_client_2022::protocol::ScoreCause;
+use socha_client_2022::protocol::{Score, ScoreCause};
 use socha_client_2022::util::SCResult;
 
-use crate::algorithms::Algorithm;
-use crate::game::{Fen, Gamestate, IGamestate, Team};
+use crate::algorithms::{Algorithm, SearchInfo};
+use crate::game::{Fen, Gamestate, IGamestate, Move, PieceType, Team};
+use crate::join::JoinMode;
+use crate::record::{GameRecord, RecordResult, RecordedMove};
+use crate::telemetry::{MoveTelemetry, TelemetryWriter};
+use crate::time_manager::{TimeConfig, TimeManager};
+use crate::watchdog::{self, SearchOutcome};
+
+/// Sent when there is no move to send, the server rejects it
+const NO_MOVE: Move = Move {
+    from: 0,
+    to: 0,
+    piece: PieceType::Herzmuschel,
+};
 
 struct ClientDelegate<E: Algorithm> {
     inner: E,
     client_team: Option<Team>,
-    time_tracker: Option<SystemTime>,
+    time_manager: TimeManager,
+    /// Since our last move was sent, to measure the opponent's time
+    enemy_clock: Option<Instant>,
+    /// Time the opponent took for its last move
+    enemy_time: Option<Duration>,
+    last_state: Option<Gamestate>,
+    record_dir: Option<PathBuf>,
+    record: Option<GameRecord>,
+    /// Our last move, until it shows up in a state update
+    pending_move: Option<RecordedMove>,
+    telemetry_dir: Option<PathBuf>,
+    /// Telemetry of the current game, created with our first move
+    telemetry: Option<TelemetryWriter>,
+}
+
+impl<E: Algorithm> ClientDelegate<E> {
+    /// Checks that the state sent by the server follows from the previous one by a legal move.
+    /// Returns: the move that was played, if any
+    fn verify_transition(&self, gamestate: &Gamestate) -> Option<Move> {
+        let last = self.last_state?;
+        if last.turn + 1 != gamestate.turn {
+            return None;
+        }
+        let played = last.move_leading_to(gamestate);
+        match played {
+            Some(mov) => info!("Played: {}", mov.notation(&last)),
+            None => warn!(
+                "No legal move leads from {} to {}",
+                last.to_fen(),
+                gamestate.to_fen()
+            ),
+        }
+        played
+    }
+
+    /// Adds the move that led to the new state to the game record, starting a
+    /// new record with the first state of the game.
+    fn record_state(
+        &mut self,
+        gamestate: &Gamestate,
+        played: Option<Move>,
+        time: Option<Duration>,
+    ) {
+        if self.record_dir.is_none() {
+            return;
+        }
+        match (&mut self.record, played) {
+            (Some(record), Some(mov)) => {
+                let recorded = match self.pending_move.take() {
+                    Some(ours) if ours.mov == mov => ours,
+                    _ => RecordedMove {
+                        mov,
+                        time,
+                        score: None,
+                    },
+                };
+                record.moves.push(recorded);
+            }
+            (Some(record), None) if record.final_state().turn == gamestate.turn => {}
+            (record, _) => {
+                if record.is_some() {
+                    warn!("Lost track of the game, restarting the record");
+                }
+                let (mut red, mut blue) = ("?".to_string(), "?".to_string());
+                match self.client_team {
+                    Some(Team::ONE) => red = self.inner.name(),
+                    Some(Team::TWO) => blue = self.inner.name(),
+                    None => {}
+                }
+                let mut new_record = GameRecord::new(*gamestate, red, blue);
+                new_record.date = Some(Local::now().format("%Y-%m-%dT%H:%M:%S").to_string());
+                *record = Some(new_record);
+            }
+        }
+    }
+
+    /// Completes the game record with the result and writes it to the record directory.
+    fn finish_record(&mut self, result: &GameResult) {
+        let (mut record, dir) = match (self.record.take(), &self.record_dir) {
+            (Some(record), Some(dir)) => (record, dir),
+            _ => return,
+        };
+        record.result = match result.winner() {
+            Some(winner) => RecordResult::Winner(winner.team().into()),
+            None => RecordResult::Draw,
+        };
+        record.cause = result
+            .scores()
+            .values()
+            .map(|score| score.cause())
+            .find(|cause| !matches!(cause, ScoreCause::Regular))
+            .or(Some(ScoreCause::Regular));
+
+        match record.write_to_dir(dir) {
+            Ok(path) => info!("Saved game record to {}", path.display()),
+            Err(err) => error!("Failed to save game record: {}", err),
+        }
+    }
+
+    /// Appends the statistics of our move to the telemetry of the game, starting a new
+    /// file with our first move.
+    fn write_telemetry(
+        &mut self,
+        gamestate: &Gamestate,
+        team: Team,
+        info: &SearchInfo,
+        took: Duration,
+    ) {
+        let dir = match &self.telemetry_dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        if self.telemetry.is_none() {
+            match TelemetryWriter::create(dir, team) {
+                Ok(writer) => {
+                    info!("Writing telemetry to {}", writer.path().display());
+                    self.telemetry = Some(writer);
+                }
+                Err(err) => {
+                    error!("Failed to create telemetry file: {}", err);
+                    // Don't try again for every move
+                    self.telemetry_dir = None;
+                    return;
+                }
+            }
+        }
+        let telemetry = MoveTelemetry::new(gamestate, team, info, took, self.enemy_time);
+        if let Some(writer) = &mut self.telemetry {
+            if let Err(err) = writer.write(&telemetry) {
+                error!("Failed to write telemetry: {}", err);
+            }
+        }
+    }
+
+    /// Makes sure the move about to be sent is legal, see [`watchdog::ensure_legal`].
+    fn ensure_legal(gamestate: &Gamestate, best: Option<Move>) -> Move {
+        watchdog::ensure_legal(gamestate, best).unwrap_or_else(|| {
+            // Whatever is sent, the server will reject it
+            error!("!!! No legal move in {} !!!", gamestate.to_fen());
+            best.unwrap_or(NO_MOVE)
+        })
+    }
 }
 
-impl<E: Algorithm> SCClientDelegate for ClientDelegate<E> {
+impl<E: Algorithm + Send + 'static> SCClientDelegate for ClientDelegate<E> {
     fn on_update_state(&mut self, state: &SCState) {
-        let gamestate: Gamestate = state.clone().into();
-        println!("{}", gamestate.to_fen());
-        println!("{}", gamestate.board);
+        let gamestate = match Gamestate::try_from(state.clone()) {
+            Ok(gamestate) => gamestate,
+            Err(err) => {
+                error!("Ignoring the state sent by the server: {}", err);
+                return;
+            }
+        };
+        debug!("{}", gamestate.to_fen());
+        debug!("\n{}", gamestate.board);
+        let played = self.verify_transition(&gamestate);
 
+        let mut enemy_time = None;
         if let Some(player) = self.client_team {
-            if let Some(time) = self.time_tracker {
-                if gamestate.current_player() == player {
+            if played.is_some() && self.last_state.map(|last| last.current_player()) == Some(player)
+            {
+                if let Some(round_trip) = self.time_manager.own_move_confirmed() {
                     info!(
-                        "Enemy took: {:?}",
-                        time.elapsed()
-                            .unwrap()
-                            .saturating_sub(Duration::from_millis(17)) //heuristic value
+                        "Round trip took {:?}, estimated overhead {:?}",
+                        round_trip,
+                        self.time_manager.overhead()
                     );
                 }
             }
+            if let Some(sent) = self.enemy_clock {
+                if gamestate.current_player() == player {
+                    let took = sent.elapsed().saturating_sub(self.time_manager.overhead());
+                    info!("Enemy took: {:?}", took);
+                    enemy_time = Some(took);
+                    self.enemy_time = enemy_time;
+                }
+            }
         }
+
+        self.record_state(&gamestate, played, enemy_time);
+        self.last_state = Some(gamestate);
     }
 
     fn on_game_end(&mut self, result: &GameResult, my_team: SCTeam) {
-        let red_score = result
-            .scores()
-            .iter()
-            .find_map(|(player, score)| {
-                if player.team() == SCTeam::One {
-                    Some(score)
-                } else {
-                    None
-                }
-            })
-            .unwrap();
-        let blue_score = result
-            .scores()
-            .iter()
-            .find_map(|(player, score)| {
-                if player.team() == SCTeam::Two {
-                    Some(score)
-                } else {
-                    None
-                }
-            })
-            .unwrap();
+        self.finish_record(result);
+        // Only the algorithm and the time manager are kept for the next game
+        self.last_state = None;
+        self.pending_move = None;
+        self.enemy_clock = None;
+        self.enemy_time = None;
+        self.telemetry = None;
+
+        let score_of = |team: SCTeam| {
+            result
+                .scores()
+                .iter()
+                .find_map(|(player, score)| (player.team() == team).then_some(score))
+        };
+        let (red_score, blue_score) = match (score_of(SCTeam::One), score_of(SCTeam::Two)) {
+            (Some(red_score), Some(blue_score)) => (red_score, blue_score),
+            _ => {
+                warn!("Incomplete game result: {:?}", result);
+                return;
+            }
+        };
+        // Parts of a score: [points, ambers]
+        let part = |score: &Score, index: usize| {
+            score
+                .parts()
+                .get(index)
+                .map_or_else(|| "?".to_string(), |part| part.to_string())
+        };
         let (my_score, enemy_score) = match my_team {
             SCTeam::One => (red_score, blue_score),
             SCTeam::Two => (blue_score, red_score),
@@ -69,19 +251,19 @@ impl<E: Algorithm> SCClientDelegate for ClientDelegate<E> {
             if my_team == winner.team() {
                 info!(
                     "WON({} : {}) -> Points({} : {})    [{:?}]",
-                    red_score.parts().get(1).unwrap(),
-                    blue_score.parts().get(1).unwrap(),
-                    red_score.parts().get(0).unwrap(),
-                    blue_score.parts().get(0).unwrap(),
+                    part(red_score, 1),
+                    part(blue_score, 1),
+                    part(red_score, 0),
+                    part(blue_score, 0),
                     enemy_score.cause()
                 );
             } else {
                 info!(
                     "LOST({} : {}) -> Points({} : {})    [{:?}]",
-                    red_score.parts().get(1).unwrap(),
-                    blue_score.parts().get(1).unwrap(),
-                    red_score.parts().get(0).unwrap(),
-                    blue_score.parts().get(0).unwrap(),
+                    part(red_score, 1),
+                    part(blue_score, 1),
+                    part(red_score, 0),
+                    part(blue_score, 0),
                     my_score.cause()
                 );
             }
@@ -89,23 +271,19 @@ impl<E: Algorithm> SCClientDelegate for ClientDelegate<E> {
             if !matches!(my_score.cause(), ScoreCause::Regular) {
                 info!(
                     "SCORE({} : {})  [{:?}]",
-                    red_score.parts().get(1).unwrap(),
-                    blue_score.parts().get(1).unwrap(),
+                    part(red_score, 1),
+                    part(blue_score, 1),
                     my_score.cause()
                 );
             } else if !matches!(enemy_score.cause(), ScoreCause::Regular) {
                 info!(
                     "SCORE({} : {})  [{:?}]",
-                    red_score.parts().get(1).unwrap(),
-                    blue_score.parts().get(1).unwrap(),
+                    part(red_score, 1),
+                    part(blue_score, 1),
                     enemy_score.cause()
                 );
             } else {
-                info!(
-                    "DRAW({}) [{:?}]",
-                    red_score.parts().get(0).unwrap(),
-                    my_score.cause()
-                );
+                info!("DRAW({}) [{:?}]", part(red_score, 0), my_score.cause());
             }
         }
     }
@@ -117,33 +295,133 @@ impl<E: Algorithm> SCClientDelegate for ClientDelegate<E> {
     }
 
     fn request_move(&mut self, sc_state: &SCState, my_team: SCTeam) -> SCMove {
-        info!("Beginning move calculation");
-        self.time_tracker = Some(SystemTime::now());
-        let best = self
-            .inner
-            .best_move(sc_state.clone().into(), my_team.into());
-        info!(
-            "Finished calculation {:?}",
-            self.time_tracker.unwrap().elapsed().unwrap()
+        self.time_manager.move_requested();
+        let gamestate = match Gamestate::try_from(sc_state.clone()) {
+            Ok(gamestate) => gamestate,
+            Err(err) => {
+                // Whatever is sent, the server will reject it
+                error!("!!! Can't search the state sent by the server: {} !!!", err);
+                return NO_MOVE.into();
+            }
+        };
+        let budget = self.time_manager.budget(&gamestate);
+        info!("Beginning move calculation with a budget of {:?}", budget);
+        self.inner.set_time_budget(budget);
+        let outcome = watchdog::search_with_deadline(
+            &mut self.inner,
+            gamestate,
+            my_team.into(),
+            self.time_manager.remaining(),
         );
-        self.time_tracker = Some(SystemTime::now());
-        let piece = best.piece;
+        let (searched, info) = match outcome {
+            SearchOutcome::Finished(mov) => (Some(mov), self.inner.search_info()),
+            SearchOutcome::TimedOut(best) | SearchOutcome::Panicked(best) => {
+                error!("!!! Search {} in {} !!!", outcome, gamestate.to_fen());
+                (best, SearchInfo::default())
+            }
+        };
+        let best = Self::ensure_legal(&gamestate, searched);
+        let took = self.time_manager.move_sent();
+        info!("Finished calculation {:?}", took);
+        self.enemy_clock = Some(Instant::now());
+        self.pending_move = Some(RecordedMove {
+            mov: best,
+            time: Some(took),
+            score: info.score,
+        });
+        self.write_telemetry(&gamestate, my_team.into(), &info, took);
+        let notation = best.notation(&gamestate);
         let mov = best.into();
-        info!("Sending move: {} {}", piece, mov);
+        info!("Sending move: {} ({})", notation, mov);
         mov
     }
 }
 
-pub struct Client<A: Algorithm> {
+/// How the client keeps playing, when a game ends or the connection fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionConfig {
+    /// Number of games to play one after another
+    pub games: usize,
+    /// Failed connections in a row, after which the client gives up
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled with every further one
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            games: 1,
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Returns: the time to wait before the given retry, counting from 0
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Everything about a client besides its algorithm.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientOptions {
+    pub join: JoinMode,
+    /// Directory to save a record of every played game into
+    pub record_dir: Option<PathBuf>,
+    /// Directory to save the telemetry of every played game into, see [`MoveTelemetry`]
+    pub telemetry_dir: Option<PathBuf>,
+    pub time: TimeConfig,
+}
+
+pub struct Client<A: Algorithm + Send + 'static> {
     inner: SCClient<ClientDelegate<A>>,
+    join: JoinMode,
 }
 
-impl<A: Algorithm> Client<A> {
-    pub fn new(algorithm: A, reservation_code: Option<String>) -> Self {
+impl<A: Algorithm + Send + 'static> Client<A> {
+    /// Creates a new client, which writes a record of each game into `record_dir`, if given.
+    pub fn new(algorithm: A, join: JoinMode, record_dir: Option<PathBuf>) -> Self {
+        Self::with_options(
+            algorithm,
+            ClientOptions {
+                join,
+                record_dir,
+                ..ClientOptions::default()
+            },
+        )
+    }
+
+    pub fn with_options(algorithm: A, options: ClientOptions) -> Self {
+        let ClientOptions {
+            join,
+            record_dir,
+            telemetry_dir,
+            time,
+        } = options;
+        let reservation_code = match &join {
+            JoinMode::Reservation(code) => Some(code.clone()),
+            JoinMode::Any | JoinMode::Room(_) => None,
+        };
         let algorithm_wrapper = ClientDelegate {
             inner: algorithm,
             client_team: None,
-            time_tracker: None,
+            time_manager: TimeManager::new(time),
+            enemy_clock: None,
+            enemy_time: None,
+            last_state: None,
+            record_dir,
+            record: None,
+            pending_move: None,
+            telemetry_dir,
+            telemetry: None,
         };
         Self {
             inner: SCClient::new(
@@ -154,11 +432,51 @@ impl<A: Algorithm> Client<A> {
                 },
                 reservation_code,
             ),
+            join,
         }
     }
 
     pub fn connect(&mut self, host: &str, port: u16) -> SCResult<GameResult> {
-        self.inner.connect(host, port)
+        info!("Joining {} on {}:{}", self.join, host, port);
+        match &self.join {
+            JoinMode::Room(room) => self.inner.connect_to_room(host, port, room),
+            JoinMode::Any | JoinMode::Reservation(_) => self.inner.connect(host, port),
+        }
+    }
+
+    /// Plays the configured number of games, reconnecting with a backoff whenever the
+    /// connection fails. Every connection joins with the same reservation code, if there
+    /// is one, so a game interrupted by a lost connection is rejoined.
+    /// The algorithm and the learned timing are kept from one game to the next.
+    /// Returns: the results of all games, or the last error once too many connections failed
+    pub fn play(
+        &mut self,
+        host: &str,
+        port: u16,
+        session: &SessionConfig,
+    ) -> SCResult<Vec<GameResult>> {
+        let mut results = vec![];
+        let mut failures = 0;
+        while results.len() < session.games {
+            match self.connect(host, port) {
+                Ok(result) => {
+                    failures = 0;
+                    results.push(result);
+                    info!("Finished game {} of {}", results.len(), session.games);
+                }
+                Err(err) => {
+                    failures += 1;
+                    if failures >= session.max_attempts {
+                        error!("Giving up after {} failed connections: {}", failures, err);
+                        return Err(err);
+                    }
+                    let backoff = session.backoff(failures - 1);
+                    warn!("Connection failed: {}, retrying in {:?}", err, backoff);
+                    thread::sleep(backoff);
+                }
+            }
+        }
+        Ok(results)
     }
 
     pub fn team(&self) -> Option<Team> {
@@ -169,3 +487,22 @@ impl<A: Algorithm> Client<A> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let session = SessionConfig {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            ..SessionConfig::default()
+        };
+        let backoffs: Vec<u64> = (0..6)
+            .map(|retry| session.backoff(retry).as_millis() as u64)
+            .collect();
+        assert_eq!(backoffs, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(session.backoff(100), Duration::from_secs(1));
+    }
+}
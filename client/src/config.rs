@@ -0,0 +1,570 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::registry::AlgorithmSettings;
+use crate::client::ClientOptions;
+use crate::join::{JoinError, JoinMode};
+use crate::logging::LogFormat;
+use crate::time_manager::TimeConfig;
+
+/// Prefix of the environment variables, which set the same options as the command line,
+/// e.g. `OSTSEESCHACH_PORT` or `OSTSEESCHACH_RECORD_DIR`. Besides, `OSTSEESCHACH_CONFIG`
+/// names the config file and `OSTSEESCHACH_LOG` filters the logged modules, see
+/// [`LOG_FILTER_VAR`](crate::logging::LOG_FILTER_VAR).
+pub const ENV_PREFIX: &str = "OSTSEESCHACH_";
+
+/// One source of settings, in which everything is optional. The layers are stacked from
+/// the defaults over the TOML file and the environment up to the command line, see
+/// [`ConfigLayer::or`].
+///
+/// ```toml
+/// [connection]
+/// host = "localhost"
+/// port = 13050
+/// room = "a1b2c3"
+/// games = 1
+///
+/// [algorithm]
+/// name = "minmax"
+/// depth = 6
+///
+/// [time]
+/// move_time_ms = 2000
+/// safety_margin_ms = 150
+///
+/// [logging]
+/// level = "info"
+/// format = "json"
+///
+/// [output]
+/// record_dir = "records"
+/// telemetry_dir = "telemetry"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigLayer {
+    pub connection: ConnectionLayer,
+    pub algorithm: AlgorithmSettings,
+    pub time: TimeLayer,
+    pub logging: LoggingLayer,
+    pub output: OutputLayer,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionLayer {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub reservation: Option<String>,
+    pub room: Option<String>,
+    /// Number of games to play one after another
+    pub games: Option<usize>,
+    /// Failed connections in a row, after which the client gives up
+    pub max_attempts: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeLayer {
+    pub move_time_ms: Option<u64>,
+    pub safety_margin_ms: Option<u64>,
+    pub initial_overhead_ms: Option<u64>,
+    pub normal_fraction: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingLayer {
+    /// One of off, error, warn, info, debug and trace
+    pub level: Option<String>,
+    pub format: Option<LogFormat>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputLayer {
+    /// Directory to save a record of every played game into
+    pub record_dir: Option<PathBuf>,
+    /// Directory to save the telemetry of every played game into
+    pub telemetry_dir: Option<PathBuf>,
+}
+
+impl ConfigLayer {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|err| ConfigError::Io {
+            path: path.to_path_buf(),
+            error: err,
+        })?;
+        text.parse()
+    }
+
+    /// Reads the settings from the environment variables starting with [`ENV_PREFIX`].
+    /// Unknown variables with the prefix are rejected like unknown keys in the file.
+    pub fn from_env<I: IntoIterator<Item = (String, String)>>(
+        vars: I,
+    ) -> Result<Self, ConfigError> {
+        let mut layer = Self::default();
+        for (var, value) in vars {
+            let name = match var.strip_prefix(ENV_PREFIX) {
+                Some(name) => name,
+                None => continue,
+            };
+            macro_rules! set {
+                ($field:expr) => {
+                    $field = Some(value.parse().map_err(|err| ConfigError::Env {
+                        var: var.clone(),
+                        message: format!("{}", err),
+                    })?)
+                };
+            }
+            match name {
+                // Read before any layer, see `ConfigLayer::config_path`
+                "CONFIG" => {}
+                // Read by the logger, see `logging::LOG_FILTER_VAR`
+                "LOG" => {}
+                "HOST" => set!(layer.connection.host),
+                "PORT" => set!(layer.connection.port),
+                "RESERVATION" => set!(layer.connection.reservation),
+                "ROOM" => set!(layer.connection.room),
+                "GAMES" => set!(layer.connection.games),
+                "MAX_ATTEMPTS" => set!(layer.connection.max_attempts),
+                "ALGORITHM" => set!(layer.algorithm.name),
+                "DEPTH" => set!(layer.algorithm.depth),
+                "MAX_TIME" => set!(layer.algorithm.max_time_ms),
+                "EVALUATION" => set!(layer.algorithm.evaluation),
+                "MOVE_TIME" => set!(layer.time.move_time_ms),
+                "SAFETY_MARGIN" => set!(layer.time.safety_margin_ms),
+                "INITIAL_OVERHEAD" => set!(layer.time.initial_overhead_ms),
+                "NORMAL_FRACTION" => set!(layer.time.normal_fraction),
+                "LOG_LEVEL" => set!(layer.logging.level),
+                "LOG_FORMAT" => set!(layer.logging.format),
+                "RECORD_DIR" => set!(layer.output.record_dir),
+                "TELEMETRY_DIR" => set!(layer.output.telemetry_dir),
+                _ => {
+                    return Err(ConfigError::Env {
+                        var,
+                        message: "Unknown setting".to_string(),
+                    })
+                }
+            }
+        }
+        Ok(layer)
+    }
+
+    /// Returns: the path of the config file given in the environment, if any
+    pub fn config_path<I: IntoIterator<Item = (String, String)>>(vars: I) -> Option<PathBuf> {
+        let config_var = format!("{}CONFIG", ENV_PREFIX);
+        vars.into_iter()
+            .find(|(var, _)| *var == config_var)
+            .map(|(_, path)| PathBuf::from(path))
+    }
+
+    /// Returns: the settings, where those of `other` take precedence
+    pub fn or(self, other: Self) -> Self {
+        // Joining by room or reservation replaces the way of joining of lower layers
+        let (reservation, room) =
+            if other.connection.reservation.is_some() || other.connection.room.is_some() {
+                (other.connection.reservation, other.connection.room)
+            } else {
+                (self.connection.reservation, self.connection.room)
+            };
+        Self {
+            connection: ConnectionLayer {
+                host: other.connection.host.or(self.connection.host),
+                port: other.connection.port.or(self.connection.port),
+                reservation,
+                room,
+                games: other.connection.games.or(self.connection.games),
+                max_attempts: other
+                    .connection
+                    .max_attempts
+                    .or(self.connection.max_attempts),
+            },
+            algorithm: self.algorithm.or(other.algorithm),
+            time: TimeLayer {
+                move_time_ms: other.time.move_time_ms.or(self.time.move_time_ms),
+                safety_margin_ms: other.time.safety_margin_ms.or(self.time.safety_margin_ms),
+                initial_overhead_ms: other
+                    .time
+                    .initial_overhead_ms
+                    .or(self.time.initial_overhead_ms),
+                normal_fraction: other.time.normal_fraction.or(self.time.normal_fraction),
+            },
+            logging: LoggingLayer {
+                level: other.logging.level.or(self.logging.level),
+                format: other.logging.format.or(self.logging.format),
+            },
+            output: OutputLayer {
+                record_dir: other.output.record_dir.or(self.output.record_dir),
+                telemetry_dir: other.output.telemetry_dir.or(self.output.telemetry_dir),
+            },
+        }
+    }
+
+    /// Fills in the defaults and checks the settings. The algorithm is only checked
+    /// once it is built, see [`AlgorithmSettings::build`].
+    pub fn resolve(self) -> Result<Config, ConfigError> {
+        let connection = ConnectionSettings {
+            host: self
+                .connection
+                .host
+                .unwrap_or_else(|| "localhost".to_string()),
+            port: self.connection.port.unwrap_or(13050),
+            reservation: self.connection.reservation,
+            room: self.connection.room,
+            games: self.connection.games.unwrap_or(1),
+            max_attempts: self.connection.max_attempts.unwrap_or(5),
+        };
+        JoinMode::from_options(connection.reservation.clone(), connection.room.clone())?
+            .check_games(connection.games)?;
+        if connection.max_attempts == 0 {
+            return Err(invalid("connection.max_attempts", "must be at least 1"));
+        }
+
+        let defaults = TimeConfig::default();
+        let time = TimeSettings {
+            move_time_ms: self
+                .time
+                .move_time_ms
+                .unwrap_or(defaults.move_time.as_millis() as u64),
+            safety_margin_ms: self
+                .time
+                .safety_margin_ms
+                .unwrap_or(defaults.safety_margin.as_millis() as u64),
+            initial_overhead_ms: self
+                .time
+                .initial_overhead_ms
+                .unwrap_or(defaults.initial_overhead.as_millis() as u64),
+            normal_fraction: self
+                .time
+                .normal_fraction
+                .unwrap_or(defaults.normal_fraction),
+        };
+        // Without overflow checks, huge values would wrap around and pass
+        match time.safety_margin_ms.checked_add(time.initial_overhead_ms) {
+            Some(reserved) if reserved < time.move_time_ms => {}
+            _ => {
+                return Err(invalid(
+                    "time",
+                    "the safety margin and the initial overhead leave no time to search",
+                ))
+            }
+        }
+        if !(time.normal_fraction > 0.0 && time.normal_fraction <= 1.0) {
+            return Err(invalid("time.normal_fraction", "must be in (0, 1]"));
+        }
+
+        let level = self.logging.level.unwrap_or_else(|| "info".to_string());
+        if LevelFilter::from_str(&level).is_err() {
+            return Err(invalid(
+                "logging.level",
+                "must be one of off, error, warn, info, debug and trace",
+            ));
+        }
+
+        let algorithm = AlgorithmSettings {
+            name: Some(
+                self.algorithm
+                    .name
+                    .unwrap_or_else(|| AlgorithmSettings::DEFAULT_ALGORITHM.to_string()),
+            ),
+            ..self.algorithm
+        };
+        Ok(Config {
+            connection,
+            algorithm,
+            time,
+            logging: LoggingSettings {
+                level,
+                format: self.logging.format.unwrap_or_default(),
+            },
+            output: OutputSettings {
+                record_dir: self.output.record_dir,
+                telemetry_dir: self.output.telemetry_dir,
+            },
+        })
+    }
+}
+
+impl FromStr for ConfigLayer {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(ConfigError::Parse)
+    }
+}
+
+fn invalid(setting: &'static str, message: &str) -> ConfigError {
+    ConfigError::Invalid {
+        setting,
+        message: message.to_string(),
+    }
+}
+
+/// The complete settings of the client with the defaults filled in, see
+/// [`ConfigLayer::resolve`]. Printed as TOML, it can be read again as a config file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Config {
+    pub connection: ConnectionSettings,
+    pub algorithm: AlgorithmSettings,
+    pub time: TimeSettings,
+    pub logging: LoggingSettings,
+    pub output: OutputSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConnectionSettings {
+    pub host: String,
+    pub port: u16,
+    pub reservation: Option<String>,
+    pub room: Option<String>,
+    pub games: usize,
+    pub max_attempts: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimeSettings {
+    pub move_time_ms: u64,
+    pub safety_margin_ms: u64,
+    pub initial_overhead_ms: u64,
+    pub normal_fraction: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoggingSettings {
+    pub level: String,
+    pub format: LogFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutputSettings {
+    pub record_dir: Option<PathBuf>,
+    pub telemetry_dir: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn client_options(&self) -> ClientOptions {
+        ClientOptions {
+            join: self.join_mode(),
+            record_dir: self.output.record_dir.clone(),
+            telemetry_dir: self.output.telemetry_dir.clone(),
+            time: self.time_config(),
+        }
+    }
+
+    pub fn join_mode(&self) -> JoinMode {
+        let connection = &self.connection;
+        JoinMode::from_options(connection.reservation.clone(), connection.room.clone())
+            .unwrap_or(JoinMode::Any)
+    }
+
+    pub fn time_config(&self) -> TimeConfig {
+        TimeConfig {
+            move_time: Duration::from_millis(self.time.move_time_ms),
+            safety_margin: Duration::from_millis(self.time.safety_margin_ms),
+            initial_overhead: Duration::from_millis(self.time.initial_overhead_ms),
+            normal_fraction: self.time.normal_fraction,
+        }
+    }
+
+    pub fn log_level(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.logging.level).unwrap_or(LevelFilter::Info)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap_or_else(|err| format!("# {}\n", err))
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// The file is no valid TOML or contains unknown or mistyped settings.
+    Parse(toml::de::Error),
+    /// An environment variable with the prefix is unknown or has an invalid value.
+    Env {
+        var: String,
+        message: String,
+    },
+    Join(JoinError),
+    /// A setting is out of its range.
+    Invalid {
+        setting: &'static str,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => {
+                write!(f, "Failed to read {}: {}", path.display(), error)
+            }
+            ConfigError::Parse(err) => write!(f, "Invalid configuration: {}", err),
+            ConfigError::Env { var, message } => write!(f, "Invalid {}: {}", var, message),
+            ConfigError::Join(err) => write!(f, "{}", err),
+            ConfigError::Invalid { setting, message } => {
+                write!(f, "Invalid {}: {}", setting, message)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<JoinError> for ConfigError {
+    fn from(err: JoinError) -> Self {
+        ConfigError::Join(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::LOG_FILTER_VAR;
+
+    fn env(vars: &[(&str, &str)]) -> Result<ConfigLayer, ConfigError> {
+        ConfigLayer::from_env(
+            vars.iter()
+                .map(|(var, value)| (var.to_string(), value.to_string())),
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let config: ConfigLayer = "[connection]\nport = 13051\nroom = \"abc\"\n"
+            .parse()
+            .unwrap();
+        assert_eq!(config.connection.port, Some(13051));
+        assert_eq!(config.connection.room.as_deref(), Some("abc"));
+        assert_eq!(config.connection.host, None);
+        assert_eq!(config.algorithm, AlgorithmSettings::default());
+
+        let config: ConfigLayer = "[algorithm]\nname = \"random\"\n".parse().unwrap();
+        assert_eq!(config.algorithm.name.as_deref(), Some("random"));
+
+        assert_eq!("".parse::<ConfigLayer>().unwrap(), ConfigLayer::default());
+        assert!("[connection]\nport = \"x\"".parse::<ConfigLayer>().is_err());
+        assert!("[connection]\nrom = \"abc\""
+            .parse::<ConfigLayer>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_env() {
+        let layer = env(&[
+            ("OSTSEESCHACH_PORT", "13052"),
+            ("OSTSEESCHACH_DEPTH", "3"),
+            ("OSTSEESCHACH_RECORD_DIR", "records"),
+            ("OSTSEESCHACH_LOG_FORMAT", "json"),
+            ("PATH", "/bin"),
+        ])
+        .unwrap();
+        assert_eq!(layer.logging.format, Some(LogFormat::Json));
+        assert_eq!(layer.connection.port, Some(13052));
+        assert_eq!(layer.algorithm.depth, Some(3));
+        assert_eq!(layer.output.record_dir, Some(PathBuf::from("records")));
+
+        assert!(matches!(
+            env(&[("OSTSEESCHACH_PORT", "many")]),
+            Err(ConfigError::Env { .. })
+        ));
+        assert!(matches!(
+            env(&[("OSTSEESCHACH_PROT", "1")]),
+            Err(ConfigError::Env { .. })
+        ));
+
+        let vars = vec![("OSTSEESCHACH_CONFIG".to_string(), "a.toml".to_string())];
+        assert_eq!(
+            ConfigLayer::config_path(vars.clone()),
+            Some(PathBuf::from("a.toml"))
+        );
+        assert_eq!(ConfigLayer::from_env(vars).unwrap(), ConfigLayer::default());
+        // Read by the logger instead
+        let log = env(&[(LOG_FILTER_VAR, "client::algorithms=trace")]).unwrap();
+        assert_eq!(log, ConfigLayer::default());
+    }
+
+    #[test]
+    fn test_precedence() {
+        let file: ConfigLayer = "[connection]\nhost = \"file\"\nport = 1\nroom = \"abc\"\n\
+                                 [logging]\nlevel = \"debug\""
+            .parse()
+            .unwrap();
+        let env = env(&[("OSTSEESCHACH_PORT", "2")]).unwrap();
+        let mut cli = ConfigLayer::default();
+        cli.connection.reservation = Some("code".to_string());
+
+        let config = ConfigLayer::default()
+            .or(file)
+            .or(env)
+            .or(cli)
+            .resolve()
+            .unwrap();
+        assert_eq!(config.connection.host, "file");
+        assert_eq!(config.connection.port, 2);
+        // The reservation replaces the room of the file instead of conflicting with it
+        assert_eq!(
+            config.join_mode(),
+            JoinMode::Reservation("code".to_string())
+        );
+        assert_eq!(config.log_level(), LevelFilter::Debug);
+        assert_eq!(config.time_config(), TimeConfig::default());
+    }
+
+    #[test]
+    fn test_validation() {
+        let resolve = |toml: &str| toml.parse::<ConfigLayer>().unwrap().resolve();
+        assert!(resolve("").is_ok());
+        assert!(matches!(
+            resolve("[connection]\nroom = \"abc\"\nreservation = \"code\""),
+            Err(ConfigError::Join(JoinError::ReservationAndRoom))
+        ));
+        assert!(matches!(
+            resolve("[connection]\nroom = \"abc\"\ngames = 2"),
+            Err(ConfigError::Join(JoinError::SingleGame { .. }))
+        ));
+        assert!(matches!(
+            resolve("[time]\nmove_time_ms = 100\nsafety_margin_ms = 100"),
+            Err(ConfigError::Invalid { .. })
+        ));
+        let overflowing = env(&[
+            ("OSTSEESCHACH_SAFETY_MARGIN", "18446744073709551615"),
+            ("OSTSEESCHACH_INITIAL_OVERHEAD", "2"),
+        ]);
+        assert!(matches!(
+            overflowing.unwrap().resolve(),
+            Err(ConfigError::Invalid { .. })
+        ));
+        assert!(matches!(
+            resolve("[time]\nnormal_fraction = 1.5"),
+            Err(ConfigError::Invalid { .. })
+        ));
+        assert!(matches!(
+            resolve("[logging]\nlevel = \"loud\""),
+            Err(ConfigError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn test_print_round_trip() {
+        let config = "[connection]\nroom = \"abc\"\n[algorithm]\ndepth = 4"
+            .parse::<ConfigLayer>()
+            .unwrap()
+            .resolve()
+            .unwrap();
+        let printed: ConfigLayer = config.to_toml().parse().unwrap();
+        assert_eq!(printed.resolve().unwrap(), config);
+    }
+}
@@ -7,7 +7,7 @@ use thincollections::thin_vec::ThinVec;
 use crate::bitboard;
 use crate::game::{
     moewe_lookup_moves, muschel_lookup_moves, robbe_lookup_moves, seestern_lookup_moves, Bitboard,
-    Move, Piece, PieceType, ShortForm, Team,
+    BoardViolation, Move, Piece, PieceType, ShortForm, Team,
 };
 use crate::utils::{bit_loop, square_of};
 
@@ -152,6 +152,35 @@ impl Board {
         }
     }
 
+    /// Returns: the board rotated by 180 degrees with the teams swapped, so every piece
+    /// stands where the enemy's counterpart would, moving in the other direction.
+    pub fn flipped(&self) -> Self {
+        let rotated = |mut bitboard: Bitboard| *bitboard.rotate180();
+        Board {
+            blue: rotated(self.red),
+            red: rotated(self.blue),
+            seesterne: rotated(self.seesterne),
+            muscheln: rotated(self.muscheln),
+            moewen: rotated(self.moewen),
+            robben: rotated(self.robben),
+            double: rotated(self.double),
+        }
+    }
+
+    /// Returns: the board mirrored horizontally, swapping the files a and h, b and g etc.
+    pub fn mirrored(&self) -> Self {
+        let mirrored = |mut bitboard: Bitboard| *bitboard.flip_horizontal();
+        Board {
+            blue: mirrored(self.blue),
+            red: mirrored(self.red),
+            seesterne: mirrored(self.seesterne),
+            muscheln: mirrored(self.muscheln),
+            moewen: mirrored(self.moewen),
+            robben: mirrored(self.robben),
+            double: mirrored(self.double),
+        }
+    }
+
     /// Returns: the piece at a specific position, or None if there is no piece
     #[inline]
     pub fn piece_at(&self, pos: u8) -> Option<Piece> {
@@ -229,7 +258,9 @@ impl Board {
     }
 
     /// Applies the given move to the board, for the specific team. Does
-    /// NOT check, whether the move is legal.
+    /// NOT check, whether the move is legal, see [`Gamestate::try_apply_move`](crate::game::Gamestate::try_apply_move).
+    /// Moving onto a piece of the own team breaks the board, which debug builds catch
+    /// with [`Board::validate`].
     /// Returns: the amount of points to add for the given move
     #[inline]
     pub fn apply_move(&mut self, game_move: &Move, team: Team) -> u8 {
@@ -299,6 +330,7 @@ impl Board {
                 if Self::on_finish_line(new_piece, team) {
                     *friendly &= !new_piece;
                     self.muscheln &= !new_piece;
+                    self.double &= !new_piece;
                     points += 1;
                 }
             }
@@ -326,6 +358,7 @@ impl Board {
                 if Self::on_finish_line(new_piece, team) {
                     *friendly &= !new_piece;
                     self.seesterne &= !new_piece;
+                    self.double &= !new_piece;
                     points += 1;
                 }
             }
@@ -353,11 +386,19 @@ impl Board {
                 if Self::on_finish_line(new_piece, team) {
                     *friendly &= !new_piece;
                     self.moewen &= !new_piece;
+                    self.double &= !new_piece;
                     points += 1;
                 }
             }
         }
 
+        debug_assert_eq!(
+            self.validate(),
+            Ok(()),
+            "{:?} of team {} broke the board",
+            game_move,
+            team
+        );
         points
     }
 
@@ -396,6 +437,17 @@ impl Board {
     /// CAUTION: DOES NOT UPDATE GAMESTATE HASH!
     #[inline]
     pub fn set_piece(&mut self, pos: u8, piece: Piece) {
+        for bitboard in [
+            &mut self.red,
+            &mut self.blue,
+            &mut self.seesterne,
+            &mut self.muscheln,
+            &mut self.moewen,
+            &mut self.robben,
+            &mut self.double,
+        ] {
+            bitboard.clear_bit(pos);
+        }
         match piece.piece_type {
             PieceType::Robbe => self.robben.set_bit(pos),
             PieceType::Herzmuschel => self.muscheln.set_bit(pos),
@@ -409,6 +461,40 @@ impl Board {
         if piece.stacked {
             self.double.set_bit(pos);
         }
+        debug_assert_eq!(
+            self.validate(),
+            Ok(()),
+            "Setting {:?} broke the board",
+            piece
+        );
+    }
+
+    /// Checks that the bitboards describe a consistent board: every occupied square has
+    /// exactly one team and one piece type, and towers only stand on occupied squares.
+    /// Returns: all violated invariants, if any
+    pub fn validate(&self) -> Result<(), Vec<BoardViolation>> {
+        let occupied = self.red | self.blue;
+        let mut typed = Bitboard::new();
+        let mut several_types = Bitboard::new();
+        for bitboard in [self.seesterne, self.muscheln, self.moewen, self.robben] {
+            several_types |= typed & bitboard;
+            typed |= bitboard;
+        }
+        let violations: Vec<_> = [
+            BoardViolation::BothTeams(self.red & self.blue),
+            BoardViolation::SeveralTypes(several_types),
+            BoardViolation::MissingType(occupied & !typed),
+            BoardViolation::MissingTeam(typed & !occupied),
+            BoardViolation::EmptyTower(self.double & !occupied),
+        ]
+        .into_iter()
+        .filter(|violation| violation.squares().bits != 0)
+        .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
     }
 
     /// Counts the amount of available moves for a certain team.<br>
@@ -469,28 +555,53 @@ impl Board {
             back: 63,
         };
     }
-}
 
-impl Display for Board {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let mut out: String = String::from("╔══════════════════════════╗\n");
+    /// Renders the board like its [`Display`] implementation, but with the ranks and
+    /// files labelled and the given squares highlighted using ANSI escape codes, e.g.
+    /// the squares of the last move.
+    pub fn render_highlighted(&self, highlighted: Bitboard) -> String {
+        self.render(highlighted, true)
+    }
+
+    fn render(&self, highlighted: Bitboard, labelled: bool) -> String {
+        let margin = if labelled { "  " } else { "" };
+        let mut out = format!("{}╔══════════════════════════╗\n", margin);
         let mut index_min = 56 + 8;
         let mut index_max = 64 + 8;
-        for _ in (0..8).rev() {
+        for rank in (0..8).rev() {
             index_max -= 8;
             index_min -= 8;
+            if labelled {
+                out.push_str(&format!("{} ", rank + 1));
+            }
             out.push_str("║  ");
             for j in index_min..index_max {
-                if let Some(piece) = self.piece_at(j) {
-                    out.push_str(&*format!("{:<3}", piece.to_short_form()))
+                let symbol = if let Some(piece) = self.piece_at(j) {
+                    piece.to_short_form()
                 } else {
-                    out.push_str("-  ");
+                    String::from("-")
                 };
+                if highlighted.get_bit(j) {
+                    out.push_str(&format!("\x1b[7m{}\x1b[0m", symbol));
+                    out.push_str(&" ".repeat(3 - symbol.len()));
+                } else {
+                    out.push_str(&format!("{:<3}", symbol));
+                }
             }
             out.push_str("║\n");
         }
+        out.push_str(margin);
         out.push_str("╚══════════════════════════╝");
-        write!(f, "{}", out)
+        if labelled {
+            out.push_str("\n     a  b  c  d  e  f  g  h");
+        }
+        out
+    }
+}
+
+impl Display for Board {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(bitboard!(), false))
     }
 }
 
@@ -551,7 +662,7 @@ mod test {
     use rand_xoshiro::Xoshiro128Plus;
 
     use crate::bitboard;
-    use crate::game::{Bitboard, Board, Move, Piece, PieceType, Team};
+    use crate::game::{Bitboard, Board, BoardViolation, Move, Piece, PieceType, Team};
 
     #[test]
     fn test_frontmost_piece() {
@@ -682,6 +793,22 @@ mod test {
         assert_eq!(board.double, bitboard!(1 << 44))
     }
 
+    #[test]
+    fn test_tower_on_finish_line() {
+        let mut board = Board::empty();
+        board.set_piece(54, Piece::new(PieceType::Seestern, Team::ONE, false));
+        board.set_piece(62, Piece::new(PieceType::Moewe, Team::TWO, false));
+
+        let m = Move {
+            from: 54,
+            to: 62,
+            piece: PieceType::Seestern,
+        };
+        assert_eq!(board.apply_move(&m, Team::ONE), 1);
+        // The tower leaves the board, so no trace of it may remain
+        assert_eq!(board, Board::empty());
+    }
+
     #[test]
     fn test_board_piece_interactions() {
         let mut board = Board::empty();
@@ -711,4 +838,52 @@ mod test {
 
         assert_eq!(board.apply_move(&m, Team::TWO), 1);
     }
+
+    #[test]
+    fn test_validate() {
+        let mut rng = Xoshiro128Plus::seed_from_u64(48);
+        let board = Board::new_random(&mut rng);
+        assert_eq!(board.validate(), Ok(()));
+
+        let mut broken = board;
+        broken.blue.set_bit(0);
+        broken.moewen.set_bit(20);
+        broken.seesterne.set_bit(63);
+        broken.muscheln.set_bit(63);
+        broken.robben.set_bit(63);
+        broken.double.set_bit(30);
+        assert_eq!(
+            broken.validate(),
+            Err(vec![
+                BoardViolation::BothTeams(bitboard!(1)),
+                BoardViolation::SeveralTypes(bitboard!(1 << 63)),
+                BoardViolation::MissingTeam(bitboard!(1 << 20)),
+                BoardViolation::EmptyTower(bitboard!(1 << 30)),
+            ])
+        );
+
+        let mut untyped = board;
+        untyped.red.set_bit(27);
+        let violations = untyped.validate().unwrap_err();
+        assert_eq!(
+            violations,
+            vec![BoardViolation::MissingType(bitboard!(1 << 27))]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "Squares of a team without a piece type: d4"
+        );
+    }
+
+    #[test]
+    fn test_set_piece_replaces() {
+        let mut board = Board::empty();
+        board.set_piece(9, Piece::new(PieceType::Robbe, Team::ONE, true));
+        board.set_piece(9, Piece::new(PieceType::Moewe, Team::TWO, false));
+        assert_eq!(board.validate(), Ok(()));
+        assert_eq!(
+            board.piece_at(9),
+            Some(Piece::new(PieceType::Moewe, Team::TWO, false))
+        );
+    }
 }
@@ -0,0 +1,253 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::game::{square_name, Bitboard, PieceType, Team};
+
+/// Errors raised by the game module, e.g. when parsing FEN strings or short forms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    /// The input ended before the named field.
+    MissingField { field: &'static str, pos: usize },
+    /// There is more input after the last expected field.
+    TrailingInput { pos: usize },
+    /// The board does not consist of exactly 8 ranks.
+    RankCount { found: usize, pos: usize },
+    /// A rank describes more than 8 squares.
+    RankOverflow { rank: u8, pos: usize },
+    /// A rank describes less than 8 squares.
+    RankTooShort { rank: u8, pos: usize },
+    /// A character which is neither a piece, a tower marker nor an amount of empty squares.
+    UnexpectedChar { found: char, pos: usize },
+    /// The round is not a number between 0 and 255.
+    BadRound { value: String, pos: usize },
+    /// The amount of ambers is not a number between 0 and 3.
+    BadPoints { value: String, pos: usize },
+    /// The version tag of an extended FEN is not supported.
+    UnknownVersion { value: String, pos: usize },
+    /// The side to move is neither `r` nor `b`.
+    BadSideToMove { value: String, pos: usize },
+    /// The side to move does not match the team on turn in the given round.
+    SideToMoveMismatch { side: Team, round: u8 },
+    /// A metadata field is not of the form `key=value`.
+    BadMetadata { value: String, pos: usize },
+    /// A metadata key or value is empty or contains whitespace, or the key contains `=`,
+    /// so it can't be written to a FEN.
+    UnwritableMetadata { key: String, value: String },
+    /// The string is not the short form of a piece or piece type.
+    UnknownShortForm(String),
+    /// A move is not written in algebraic notation, see [`Move`](crate::game::Move).
+    BadNotation { value: String, pos: usize },
+    /// The annotations of a move in algebraic notation don't match the gamestate.
+    NotationMismatch { notation: String, expected: String },
+    /// The string does not name a variant of an enum.
+    UnknownVariant(String),
+    /// A move was rejected, see [`MoveError`].
+    IllegalMove(MoveError),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::MissingField { field, pos } => {
+                write!(f, "Expected {} at position {}", field, pos)
+            }
+            GameError::TrailingInput { pos } => {
+                write!(f, "Unexpected trailing input at position {}", pos)
+            }
+            GameError::RankCount { found, pos } => write!(
+                f,
+                "Expected 8 ranks, but found {} at position {}",
+                found, pos
+            ),
+            GameError::RankOverflow { rank, pos } => write!(
+                f,
+                "Rank {} describes more than 8 squares at position {}",
+                rank, pos
+            ),
+            GameError::RankTooShort { rank, pos } => write!(
+                f,
+                "Rank {} describes less than 8 squares at position {}",
+                rank, pos
+            ),
+            GameError::UnexpectedChar { found, pos } => {
+                write!(f, "Unexpected character '{}' at position {}", found, pos)
+            }
+            GameError::BadRound { value, pos } => write!(
+                f,
+                "Invalid round '{}' at position {}, expected 0 to 255",
+                value, pos
+            ),
+            GameError::BadPoints { value, pos } => write!(
+                f,
+                "Invalid amount of ambers '{}' at position {}, expected 0 to 3",
+                value, pos
+            ),
+            GameError::UnknownVersion { value, pos } => {
+                write!(f, "Unsupported FEN version '{}' at position {}", value, pos)
+            }
+            GameError::BadSideToMove { value, pos } => write!(
+                f,
+                "Invalid side to move '{}' at position {}, expected 'r' or 'b'",
+                value, pos
+            ),
+            GameError::SideToMoveMismatch { side, round } => {
+                write!(f, "Team {} can't be on turn in round {}", side, round)
+            }
+            GameError::BadMetadata { value, pos } => write!(
+                f,
+                "Invalid metadata '{}' at position {}, expected key=value",
+                value, pos
+            ),
+            GameError::UnwritableMetadata { key, value } => write!(
+                f,
+                "Metadata '{}={}' can't be written to a FEN, expected a key without '=' and a value, both without whitespace",
+                key, value
+            ),
+            GameError::BadNotation { value, pos } => {
+                write!(f, "Invalid move notation '{}' at position {}", value, pos)
+            }
+            GameError::NotationMismatch { notation, expected } => {
+                write!(f, "The move {} should be written as {}", notation, expected)
+            }
+            GameError::UnknownShortForm(s) => write!(f, "Unknown short form {}", s),
+            GameError::UnknownVariant(s) => write!(f, "{}", s),
+            GameError::IllegalMove(err) => write!(f, "Illegal move: {}", err),
+        }
+    }
+}
+
+impl Error for GameError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GameError::IllegalMove(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<MoveError> for GameError {
+    fn from(err: MoveError) -> Self {
+        GameError::IllegalMove(err)
+    }
+}
+
+/// Reasons for a move being rejected by [`Gamestate::try_apply_move`](crate::game::Gamestate::try_apply_move).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// The game has already ended, no more moves may be played.
+    GameOver,
+    /// One of the move's positions lies outside of the board.
+    OutOfBounds(u8),
+    /// There is no piece on the origin square.
+    NoPieceAt(u8),
+    /// The piece on the origin square belongs to the team that is not on turn.
+    WrongTeam {
+        pos: u8,
+        expected: Team,
+        found: Team,
+    },
+    /// The move claims a different piece type than the one on the origin square.
+    WrongPieceType {
+        pos: u8,
+        claimed: PieceType,
+        found: PieceType,
+    },
+    /// The piece on the origin square cannot reach the target square.
+    Unreachable { from: u8, to: u8, piece: PieceType },
+    /// The target square is occupied by a piece of the moving team.
+    OwnPieceOnTarget(u8),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::GameOver => write!(f, "The game is already over"),
+            MoveError::OutOfBounds(pos) => write!(f, "Position {} is not on the board", pos),
+            MoveError::NoPieceAt(pos) => write!(f, "There is no piece at {}", square_name(*pos)),
+            MoveError::WrongTeam {
+                pos,
+                expected,
+                found,
+            } => write!(
+                f,
+                "The piece at {} belongs to team {}, but team {} is on turn",
+                square_name(*pos),
+                found,
+                expected
+            ),
+            MoveError::WrongPieceType {
+                pos,
+                claimed,
+                found,
+            } => write!(
+                f,
+                "The piece at {} is a {}, not a {}",
+                square_name(*pos),
+                found,
+                claimed
+            ),
+            MoveError::Unreachable { from, to, piece } => write!(
+                f,
+                "A {} at {} cannot move to {}",
+                piece,
+                square_name(*from),
+                square_name(*to)
+            ),
+            MoveError::OwnPieceOnTarget(pos) => write!(
+                f,
+                "The target {} is occupied by an own piece",
+                square_name(*pos)
+            ),
+        }
+    }
+}
+
+impl Error for MoveError {}
+
+/// An inconsistency between the bitboards of a [`Board`](crate::game::Board), see
+/// [`Board::validate`](crate::game::Board::validate). Each holds the squares affected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoardViolation {
+    /// Squares owned by both teams.
+    BothTeams(Bitboard),
+    /// Squares with more than one piece type.
+    SeveralTypes(Bitboard),
+    /// Squares of a team without any piece type.
+    MissingType(Bitboard),
+    /// Squares with a piece type, but without a team.
+    MissingTeam(Bitboard),
+    /// Towers on empty squares.
+    EmptyTower(Bitboard),
+}
+
+impl BoardViolation {
+    pub fn squares(&self) -> Bitboard {
+        match *self {
+            BoardViolation::BothTeams(squares)
+            | BoardViolation::SeveralTypes(squares)
+            | BoardViolation::MissingType(squares)
+            | BoardViolation::MissingTeam(squares)
+            | BoardViolation::EmptyTower(squares) => squares,
+        }
+    }
+}
+
+impl fmt::Display for BoardViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            BoardViolation::BothTeams(_) => "Squares owned by both teams",
+            BoardViolation::SeveralTypes(_) => "Squares with several piece types",
+            BoardViolation::MissingType(_) => "Squares of a team without a piece type",
+            BoardViolation::MissingTeam(_) => "Squares with a piece type, but no team",
+            BoardViolation::EmptyTower(_) => "Towers on empty squares",
+        };
+        let squares = self.squares();
+        let names: Vec<String> = (0..64)
+            .filter(|pos| squares.get_bit(*pos))
+            .map(square_name)
+            .collect();
+        write!(f, "{}: {}", description, names.join(", "))
+    }
+}
+
+impl Error for BoardViolation {}
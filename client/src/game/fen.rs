@@ -1,7 +1,11 @@
-use crate::game::{Board, Gamestate, Piece, PieceType, ShortForm, Team, Tile};
-use lazy_static::lazy_static;
-use regex::Regex;
-use socha_client_2022::util::{SCError, SCResult};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::game::{
+    Board, GameError, Gamestate, IGamestate, Piece, PieceType, ShortForm, Team, Tile,
+    MAX_AMBERS,
+};
 
 pub trait Fen: Sized {
     type Err;
@@ -9,109 +13,555 @@ pub trait Fen: Sized {
     fn load_fen(fen: &str) -> Result<Self, Self::Err>;
 }
 
-static FEN_REGEX_STRING: &str = r"^(?P<pieces>(?P<r1>(?:[1-8]|[msrhMSRH]\*?){1,8})/(?P<r2>(?:[1-8]|[msrhMSRH]\*?){1,8})/(?P<r3>(?:[1-8]|[msrhMSRH]\*?){1,8})/(?P<r4>(?:[1-8]|[msrhMSRH]\*?){1,8})/(?P<r5>(?:[1-8]|[msrhMSRH]\*?){1,8})/(?P<r6>(?:[1-8]|[msrhMSRH]\*?){1,8})/(?P<r7>(?:[1-8]|[msrhMSRH]\*?){1,8})/(?P<r8>(?:[1-8]|[msrhMSRH]\*?){1,8})) (?P<round>(?:[1-5]?[0-9]|60)) (?P<points>(?P<pt_red>[0-3])/(?P<pt_blu>[0-3]))$";
+/// Version of the extended FEN format written by [`ExtendedFen`].
+pub const FEN_VERSION: u8 = 1;
+
+/// Metadata key under which the id of the starting position is stored.
+pub const START_POSITION_KEY: &str = "start";
+
+/// A gamestate in the versioned, extended FEN format:
+///
+/// `v1 <pieces> <side to move> <round> <ambers red>/<ambers blue> [<key>=<value> ...]`
+///
+/// The side to move is `r` for [`Team::ONE`] or `b` for [`Team::TWO`]. It is kept as
+/// written, even if it isn't the team on turn in the given round, which only
+/// [`Gamestate::load_fen`] rejects. Optional metadata, like the id of the starting
+/// position, is appended as `key=value` pairs. The original format
+/// `<pieces> <round> <ambers red>/<ambers blue>` is still accepted when parsing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedFen {
+    pub state: Gamestate,
+    pub side: Team,
+    metadata: BTreeMap<String, String>,
+}
+
+impl ExtendedFen {
+    /// Wraps a gamestate without any metadata, with the team on turn as side to move.
+    pub fn new(state: Gamestate) -> Self {
+        Self {
+            state,
+            side: state.current_player(),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Adds a metadata entry, replacing any previous value of the key.
+    /// Returns: an error, if the entry wouldn't survive being written and parsed again
+    pub fn insert_metadata<K: ToString, V: ToString>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<(), GameError> {
+        let (key, value) = (key.to_string(), value.to_string());
+        if !is_metadata_key(&key) || !is_metadata_value(&value) {
+            return Err(GameError::UnwritableMetadata { key, value });
+        }
+        self.metadata.insert(key, value);
+        Ok(())
+    }
+
+    /// Returns: the id of the starting position, if known
+    pub fn start_position(&self) -> Option<&str> {
+        self.metadata.get(START_POSITION_KEY).map(String::as_str)
+    }
+
+    /// Attaches the id of the starting position, e.g. the seed it was generated from.
+    pub fn with_start_position<S: ToString>(mut self, id: S) -> Result<Self, GameError> {
+        self.insert_metadata(START_POSITION_KEY, id)?;
+        Ok(self)
+    }
+}
+
+fn is_metadata_key(key: &str) -> bool {
+    is_metadata_value(key) && !key.contains('=')
+}
+
+fn is_metadata_value(value: &str) -> bool {
+    !value.is_empty() && !value.contains(char::is_whitespace)
+}
+
+impl fmt::Display for ExtendedFen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = &self.state;
+        write!(
+            f,
+            "v{} {} {} {} {}/{}",
+            FEN_VERSION,
+            pieces_to_fen(&state.board),
+            side_to_fen(self.side),
+            state.turn,
+            state.ambers[0],
+            state.ambers[1]
+        )?;
+        for (key, value) in self.metadata.iter() {
+            write!(f, " {}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ExtendedFen {
+    type Err = GameError;
+
+    fn from_str(fen: &str) -> Result<Self, GameError> {
+        let mut fields = split_with_offsets(fen, ' ', 0).peekable();
+
+        let extended = match fields.peek() {
+            Some((pos, field)) if field.starts_with('v') => {
+                if field[1..] != FEN_VERSION.to_string() {
+                    return Err(GameError::UnknownVersion {
+                        value: field.to_string(),
+                        pos: *pos,
+                    });
+                }
+                fields.next();
+                true
+            }
+            _ => false,
+        };
+
+        let (pieces_pos, pieces) = next_field(&mut fields, "pieces", fen)?;
+        let board = parse_board(pieces, pieces_pos)?;
+
+        let side = if extended {
+            let (pos, side) = next_field(&mut fields, "side to move", fen)?;
+            Some(match side {
+                "r" => Team::ONE,
+                "b" => Team::TWO,
+                _ => {
+                    return Err(GameError::BadSideToMove {
+                        value: side.to_string(),
+                        pos,
+                    })
+                }
+            })
+        } else {
+            None
+        };
+
+        let (round_pos, round) = next_field(&mut fields, "round", fen)?;
+        let round = parse_bounded(round, u8::MAX).ok_or_else(|| GameError::BadRound {
+            value: round.to_string(),
+            pos: round_pos,
+        })?;
+
+        let (points_pos, points) = next_field(&mut fields, "points", fen)?;
+        let mut points = split_with_offsets(points, '/', points_pos);
+        let mut ambers = [0; 2];
+        for amber in ambers.iter_mut() {
+            let (pos, value) = next_field(&mut points, "points", fen)?;
+            *amber = parse_bounded(value, MAX_AMBERS).ok_or_else(|| GameError::BadPoints {
+                value: value.to_string(),
+                pos,
+            })?;
+        }
+        if let Some((pos, _)) = points.next() {
+            return Err(GameError::TrailingInput { pos });
+        }
+
+        let mut metadata = BTreeMap::new();
+        for (pos, field) in fields {
+            if !extended {
+                return Err(GameError::TrailingInput { pos });
+            }
+            match field.split_once('=') {
+                Some((key, value)) if is_metadata_key(key) && is_metadata_value(value) => {
+                    metadata.insert(key.to_string(), value.to_string());
+                }
+                _ => {
+                    return Err(GameError::BadMetadata {
+                        value: field.to_string(),
+                        pos,
+                    })
+                }
+            }
+        }
 
-lazy_static! {
-    pub static ref FEN_REGEX: Regex = Regex::new(FEN_REGEX_STRING).unwrap();
+        let state = Gamestate::new_with(board, round, ambers);
+        Ok(ExtendedFen {
+            state,
+            side: side.unwrap_or_else(|| state.current_player()),
+            metadata,
+        })
+    }
 }
 
 impl Fen for Gamestate {
-    type Err = SCError;
+    type Err = GameError;
 
     fn to_fen(&self) -> String {
-        let mut fen = String::new();
-
-        let iter = &mut self.board.iter_tiles();
-        let v: Vec<_> = iter.collect();
-
-        for row in v.rchunks(8) {
-            let mut empty = 0;
-            for tile in row.iter() {
-                match tile {
-                    Tile::Empty => empty += 1,
-                    Tile::Piece(p) => {
-                        if empty > 0 {
-                            fen.push(char::from_digit(empty, 10).unwrap());
-                            empty = 0;
-                        }
-                        fen.push_str(&*p.to_short_form());
+        ExtendedFen::new(*self).to_string()
+    }
+
+    /// Fails if the side to move isn't the team on turn in the given round, as the
+    /// gamestate derives the team on turn from the round.
+    fn load_fen(fen: &str) -> Result<Self, GameError> {
+        let fen = fen.parse::<ExtendedFen>()?;
+        if fen.side != fen.state.current_player() {
+            return Err(GameError::SideToMoveMismatch {
+                side: fen.side,
+                round: fen.state.turn,
+            });
+        }
+        Ok(fen.state)
+    }
+}
+
+/// Writes the piece placement of a FEN, starting with the 8th rank.
+pub(crate) fn pieces_to_fen(board: &Board) -> String {
+    let mut fen = String::new();
+
+    let iter = &mut board.iter_tiles();
+    let v: Vec<_> = iter.collect();
+
+    for row in v.rchunks(8) {
+        let mut empty = 0;
+        for tile in row.iter() {
+            match tile {
+                Tile::Empty => empty += 1,
+                Tile::Piece(p) => {
+                    if empty > 0 {
+                        fen.push(char::from_digit(empty, 10).unwrap());
+                        empty = 0;
                     }
+                    fen.push_str(&p.to_short_form());
                 }
             }
-            if empty > 0 {
-                fen.push(char::from_digit(empty, 10).unwrap());
-            }
-            fen.push('/');
         }
-        fen.pop().unwrap();
-        fen.push_str(&*format!(
-            " {} {}/{}",
-            self.turn, self.ambers[0], self.ambers[1]
-        ));
-        fen
-    }
-
-    fn load_fen(fen: &str) -> SCResult<Self> {
-        if !FEN_REGEX.is_match(fen) {
-            return Err(SCError::Custom(format!(
-                "Input does not match FEN Specification: {}",
-                fen
-            )));
+        if empty > 0 {
+            fen.push(char::from_digit(empty, 10).unwrap());
         }
-        let captures = FEN_REGEX.captures(fen).unwrap();
-        let mut board = Board::empty();
+        fen.push('/');
+    }
+    fen.pop();
+    fen
+}
+
+fn side_to_fen(team: Team) -> char {
+    match team {
+        Team::ONE => 'r',
+        Team::TWO => 'b',
+    }
+}
+
+/// Takes the next field, or fails with the position of the end of the input.
+fn next_field<'a, I: Iterator<Item = (usize, &'a str)>>(
+    fields: &mut I,
+    field: &'static str,
+    fen: &str,
+) -> Result<(usize, &'a str), GameError> {
+    fields.next().ok_or(GameError::MissingField {
+        field,
+        pos: fen.len(),
+    })
+}
+
+/// Splits `s` at every `separator`, pairing each part with its byte offset in the
+/// whole input, which starts `base` bytes before `s`.
+fn split_with_offsets(
+    s: &str,
+    separator: char,
+    base: usize,
+) -> impl Iterator<Item = (usize, &str)> {
+    s.split(separator).scan(base, move |offset, part| {
+        let start = *offset;
+        *offset += part.len() + separator.len_utf8();
+        Some((start, part))
+    })
+}
+
+/// Parses a decimal number without sign, which must not exceed `max`.
+fn parse_bounded(s: &str, max: u8) -> Option<u8> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse::<u8>().ok().filter(|value| *value <= max)
+}
 
-        for i in 0..8 {
-            let row = captures.get(i + 2).unwrap().as_str();
-            let mut pos_x = 0;
-            for j in 0..row.len() {
-                let piece = row.chars().nth(j).unwrap();
+/// Parses the piece placement of a FEN, starting with the 8th rank.
+pub(crate) fn parse_board(pieces: &str, base: usize) -> Result<Board, GameError> {
+    let ranks: Vec<_> = split_with_offsets(pieces, '/', base).collect();
+    if ranks.len() != 8 {
+        return Err(GameError::RankCount {
+            found: ranks.len(),
+            pos: base,
+        });
+    }
+
+    let mut board = Board::empty();
+    for (i, (rank_pos, rank)) in ranks.into_iter().enumerate() {
+        let rank_index = 7 - i as u8;
+        let mut pos_x = 0;
+        let mut chars = rank.char_indices().peekable();
+
+        while let Some((offset, c)) = chars.next() {
+            let pos = rank_pos + offset;
+            if pos_x >= 8 {
+                return Err(GameError::RankOverflow {
+                    rank: rank_index + 1,
+                    pos,
+                });
+            }
 
-                if piece.is_digit(10) {
-                    pos_x += piece.to_digit(10).unwrap();
-                    continue;
+            if let Some(empty) = c.to_digit(10).filter(|d| (1..=8).contains(d)) {
+                if pos_x + empty > 8 {
+                    return Err(GameError::RankOverflow {
+                        rank: rank_index + 1,
+                        pos,
+                    });
                 }
+                pos_x += empty;
+                continue;
+            }
+
+            let piece_type = PieceType::from_short_form(&c)
+                .map_err(|_| GameError::UnexpectedChar { found: c, pos })?;
+            let stacked = chars.next_if(|(_, next)| *next == '*').is_some();
 
-                let piece_type = PieceType::from_short_form(&piece.to_ascii_lowercase()).unwrap();
+            let piece = Piece {
+                piece_type,
+                team: if c.is_uppercase() {
+                    Team::ONE
+                } else {
+                    Team::TWO
+                },
+                stacked,
+            };
 
-                let next = row.chars().nth(j + 1);
-                let double = next.is_some() && next.unwrap() == '*';
+            board.set_piece(8 * rank_index + pos_x as u8, piece);
+            pos_x += 1;
+        }
+
+        if pos_x < 8 {
+            return Err(GameError::RankTooShort {
+                rank: rank_index + 1,
+                pos: rank_pos,
+            });
+        }
+    }
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::SliceRandom;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro128Plus;
 
-                let piece = Piece {
-                    piece_type,
-                    team: if piece.is_uppercase() {
-                        Team::ONE
-                    } else {
-                        Team::TWO
-                    },
-                    stacked: double,
-                };
+    #[test]
+    fn test_fen_round_trip() {
+        for seed in 0..20 {
+            let mut rng = Xoshiro128Plus::seed_from_u64(seed);
+            let mut gamestate = Gamestate::new_random(&mut rng);
 
-                board.set_piece(8 * (7 - i) as u8 + pos_x as u8, piece);
-                pos_x += 1;
+            while !gamestate.game_over() {
+                let legacy = format!(
+                    "{} {} {}/{}",
+                    pieces_to_fen(&gamestate.board),
+                    gamestate.turn,
+                    gamestate.ambers[0],
+                    gamestate.ambers[1]
+                );
+                for fen in [gamestate.to_fen(), legacy] {
+                    let loaded = Gamestate::load_fen(&fen).unwrap();
+                    assert_eq!(loaded.board, gamestate.board);
+                    assert_eq!(loaded.turn, gamestate.turn);
+                    assert_eq!(loaded.ambers, gamestate.ambers);
+                    assert_eq!(loaded.hash, gamestate.hash);
+                    assert_eq!(loaded.to_fen(), gamestate.to_fen());
+                }
+
+                let moves = gamestate.available_moves_current_player();
+                gamestate.apply_move(moves.choose(&mut rng).unwrap());
             }
         }
+    }
 
-        let round = captures
-            .name("round")
-            .unwrap()
-            .as_str()
-            .parse::<u8>()
-            .unwrap();
-        let points_red = captures
-            .name("pt_red")
-            .unwrap()
-            .as_str()
-            .parse::<u8>()
-            .unwrap();
-        let points_blu = captures
-            .name("pt_blu")
-            .unwrap()
-            .as_str()
-            .parse::<u8>()
+    #[test]
+    fn test_extended_fen() {
+        let mut board = Board::empty();
+        board.set_piece(0, Piece::new(PieceType::Robbe, Team::ONE, true));
+        board.set_piece(63, Piece::new(PieceType::Moewe, Team::TWO, false));
+        let gamestate = Gamestate::new_with(board, 7, [1, 0]);
+
+        let fen = ExtendedFen::new(gamestate).with_start_position(42).unwrap();
+        let written = fen.to_string();
+        assert_eq!(written, "v1 7m/8/8/8/8/8/8/R*7 b 7 1/0 start=42");
+
+        let parsed = written.parse::<ExtendedFen>().unwrap();
+        assert_eq!(parsed.state.board, board);
+        assert_eq!(parsed.start_position(), Some("42"));
+        assert_eq!(parsed, fen);
+
+        let legacy = "7m/8/8/8/8/8/8/R*7 7 1/0".parse::<ExtendedFen>().unwrap();
+        assert_eq!(legacy.state.board, board);
+        assert_eq!(legacy.side, Team::TWO);
+        assert_eq!(legacy.start_position(), None);
+
+        let late = "v1 7m/8/8/8/8/8/8/R*7 r 200 3/3"
+            .parse::<ExtendedFen>()
             .unwrap();
+        assert_eq!(late.state.turn, 200);
+        assert_eq!(late.to_string(), "v1 7m/8/8/8/8/8/8/R*7 r 200 3/3");
+
+        let wrong_side = "v1 7m/8/8/8/8/8/8/R*7 r 7 1/0";
+        let parsed = wrong_side.parse::<ExtendedFen>().unwrap();
+        assert_eq!(parsed.side, Team::ONE);
+        assert_eq!(parsed.state.current_player(), Team::TWO);
+        assert_eq!(parsed.to_string(), wrong_side);
+    }
 
-        let state = Gamestate::new_with(board, round, [points_red, points_blu]);
-        Ok(state)
+    #[test]
+    fn test_metadata() {
+        let mut fen = ExtendedFen::new(Gamestate::new_with(Board::empty(), 0, [0, 0]));
+        fen.insert_metadata("event", "a=b").unwrap();
+        for (key, value) in [
+            ("", "1"),
+            ("a=b", "1"),
+            ("a b", "1"),
+            ("a", ""),
+            ("a", "x\ty"),
+        ] {
+            assert_eq!(
+                fen.insert_metadata(key, value),
+                Err(GameError::UnwritableMetadata {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+            );
+        }
+        assert!(fen.clone().with_start_position("seed 1").is_err());
+
+        let written = fen.to_string();
+        assert_eq!(written, "v1 8/8/8/8/8/8/8/8 r 0 0/0 event=a=b");
+        assert_eq!(written.parse::<ExtendedFen>().unwrap(), fen);
+    }
+
+    #[test]
+    fn test_malformed_fens() {
+        let cases = [
+            ("", GameError::RankCount { found: 1, pos: 0 }),
+            (
+                "8/8/8/8/8/8/8/8",
+                GameError::MissingField {
+                    field: "round",
+                    pos: 15,
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/8 0",
+                GameError::MissingField {
+                    field: "points",
+                    pos: 17,
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8 0 0/0",
+                GameError::RankCount { found: 7, pos: 0 },
+            ),
+            (
+                "8/8/8/44R/8/8/8/8 0 0/0",
+                GameError::RankOverflow { rank: 5, pos: 8 },
+            ),
+            (
+                "8/8/8/8/71R/8/8/8 0 0/0",
+                GameError::RankOverflow { rank: 4, pos: 10 },
+            ),
+            (
+                "8/8/8/8/8/8/8/7x 0 0/0",
+                GameError::UnexpectedChar {
+                    found: 'x',
+                    pos: 15,
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/*7 0 0/0",
+                GameError::UnexpectedChar {
+                    found: '*',
+                    pos: 14,
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/8 256 0/0",
+                GameError::BadRound {
+                    value: "256".to_string(),
+                    pos: 16,
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/8 -1 0/0",
+                GameError::BadRound {
+                    value: "-1".to_string(),
+                    pos: 16,
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/8 0 0/4",
+                GameError::BadPoints {
+                    value: "4".to_string(),
+                    pos: 20,
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/8 0 0",
+                GameError::MissingField {
+                    field: "points",
+                    pos: 19,
+                },
+            ),
+            (
+                "8/8/8/8/8/8/8/8 0 0/0 1",
+                GameError::TrailingInput { pos: 22 },
+            ),
+            (
+                "8/8/8/8/8/8/8/8 0 0/0 start=1",
+                GameError::TrailingInput { pos: 22 },
+            ),
+            (
+                "8/8/8/8/8/8/8/7 0 0/0",
+                GameError::RankTooShort { rank: 1, pos: 14 },
+            ),
+            (
+                "8/8/8/8/8/8/8/8/8 0 0/0",
+                GameError::RankCount { found: 9, pos: 0 },
+            ),
+            (
+                "v2 8/8/8/8/8/8/8/8 r 0 0/0",
+                GameError::UnknownVersion {
+                    value: "v2".to_string(),
+                    pos: 0,
+                },
+            ),
+            (
+                "v1 8/8/8/8/8/8/8/8 0 0/0",
+                GameError::BadSideToMove {
+                    value: "0".to_string(),
+                    pos: 19,
+                },
+            ),
+            (
+                "v1 8/8/8/8/8/8/8/8 b 0 0/0",
+                GameError::SideToMoveMismatch {
+                    side: Team::TWO,
+                    round: 0,
+                },
+            ),
+            (
+                "v1 8/8/8/8/8/8/8/8 r 0 0/0 start",
+                GameError::BadMetadata {
+                    value: "start".to_string(),
+                    pos: 27,
+                },
+            ),
+        ];
+
+        for (fen, expected) in cases {
+            assert_eq!(Gamestate::load_fen(fen).unwrap_err(), expected, "{}", fen);
+        }
     }
 }
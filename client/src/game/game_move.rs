@@ -1,6 +1,20 @@
-use crate::game::{IMove, PieceType};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Copy, Clone, Debug)]
+use crate::game::{GameError, Gamestate, IGamestate, IMove, MoveError, PieceType, ShortForm};
+
+/// A move in the algebraic notation `<piece><from><separator><to><annotations>`:
+///
+/// * the piece letter is the uppercase short form of the moving piece, e.g. `R` for a Robbe
+/// * squares are written as file `a`-`h` and rank `1`-`8`, where `a1` is position 0
+/// * the separator is `x` for a capture and `-` otherwise
+/// * `*` marks the formation of a tower, every `+` an amber scored by the move
+///
+/// E.g. `Ra1-b3`, `Mc4xc5*` or `Sd7-d8+`. The annotations depend on the gamestate, so
+/// [`Display`](fmt::Display) only writes the piece and squares, while [`Move::notation`]
+/// and [`Move::from_notation`] resolve the full notation against a gamestate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "game-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Move {
     pub from: u8,
     pub to: u8,
@@ -8,3 +22,308 @@ pub struct Move {
 }
 
 impl IMove for Move {}
+
+/// Returns: the name of the square at the given position, e.g. `a1` for 0
+pub fn square_name(pos: u8) -> String {
+    format!("{}{}", (b'a' + pos % 8) as char, pos / 8 + 1)
+}
+
+/// Returns: the position of a square given by name, e.g. 0 for `a1`
+pub fn parse_square(name: &str) -> Option<u8> {
+    match name.as_bytes() {
+        [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some((rank - b'1') * 8 + (file - b'a')),
+        _ => None,
+    }
+}
+
+impl Move {
+    /// Returns: the same move on the [flipped](crate::game::Board::flipped) board
+    pub fn flipped(&self) -> Self {
+        Move {
+            from: 63 - self.from,
+            to: 63 - self.to,
+            piece: self.piece,
+        }
+    }
+
+    /// Returns: the same move on the [mirrored](crate::game::Board::mirrored) board
+    pub fn mirrored(&self) -> Self {
+        Move {
+            from: self.from ^ 7,
+            to: self.to ^ 7,
+            piece: self.piece,
+        }
+    }
+
+    /// Returns: the full notation of this move, including the annotations for
+    /// captures, towers and scored ambers when played in the given gamestate.
+    /// Illegal moves are written without annotations.
+    pub fn notation(&self, state: &Gamestate) -> String {
+        if !state.is_legal(self) {
+            return self.to_string();
+        }
+        let annotations = Annotations::of(self, state);
+
+        let mut out = format!(
+            "{}{}{}{}",
+            self.piece.to_short_form().to_ascii_uppercase(),
+            square_name(self.from),
+            if annotations.capture { 'x' } else { '-' },
+            square_name(self.to)
+        );
+        if annotations.tower {
+            out.push('*');
+        }
+        for _ in 0..annotations.points {
+            out.push('+');
+        }
+        out
+    }
+
+    /// Parses a move in algebraic notation and resolves it against the given gamestate.
+    /// The piece letter may be omitted, the piece is then taken from the board. The
+    /// separator counts as an annotation: if the notation contains a separator, `*` or
+    /// `+`, all annotations have to match the move, so `Ra1-c2` is rejected for a capture
+    /// and `Ra1xc2` for a capture forming a tower. Without any of them, like in `a1c2`,
+    /// the move is taken as it is.
+    pub fn from_notation(s: &str, state: &Gamestate) -> Result<Move, GameError> {
+        let parsed = ParsedMove::parse(s)?;
+        let piece = match parsed.piece {
+            Some(piece) => piece,
+            None => state
+                .board
+                .piece_at(parsed.from)
+                .map(|piece| piece.piece_type)
+                .ok_or(MoveError::NoPieceAt(parsed.from))?,
+        };
+        let mov = Move {
+            from: parsed.from,
+            to: parsed.to,
+            piece,
+        };
+        state.validate_move(&mov)?;
+
+        if parsed.annotated && parsed.annotations != Annotations::of(&mov, state) {
+            return Err(GameError::NotationMismatch {
+                notation: s.to_string(),
+                expected: mov.notation(state),
+            });
+        }
+        Ok(mov)
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}-{}",
+            self.piece.to_short_form().to_ascii_uppercase(),
+            square_name(self.from),
+            square_name(self.to)
+        )
+    }
+}
+
+impl FromStr for Move {
+    type Err = GameError;
+
+    /// Parses a move without resolving it against a gamestate, so the piece letter is
+    /// required and annotations are ignored.
+    fn from_str(s: &str) -> Result<Self, GameError> {
+        let parsed = ParsedMove::parse(s)?;
+        let piece = parsed.piece.ok_or(GameError::BadNotation {
+            value: s.to_string(),
+            pos: 0,
+        })?;
+        Ok(Move {
+            from: parsed.from,
+            to: parsed.to,
+            piece,
+        })
+    }
+}
+
+/// The annotations of a move, i.e. what happens when it is played.
+#[derive(Debug, Default, PartialEq)]
+struct Annotations {
+    capture: bool,
+    tower: bool,
+    points: u8,
+}
+
+impl Annotations {
+    /// Plays the move, which has to be legal, to find out its annotations.
+    fn of(mov: &Move, state: &Gamestate) -> Self {
+        let team = state.current_player();
+        let capture = state.board.player_pieces(!team).get_bit(mov.to);
+
+        let mut child = *state;
+        child.apply_move(mov);
+        Annotations {
+            capture,
+            tower: capture && child.board.double.get_bit(mov.to),
+            points: child.ambers[team as usize].saturating_sub(state.ambers[team as usize]),
+        }
+    }
+}
+
+/// The syntactic parts of a move in algebraic notation.
+struct ParsedMove {
+    piece: Option<PieceType>,
+    from: u8,
+    to: u8,
+    /// Whether a separator or any annotation was written.
+    annotated: bool,
+    annotations: Annotations,
+}
+
+impl ParsedMove {
+    fn parse(s: &str) -> Result<Self, GameError> {
+        let bad = |pos: usize| GameError::BadNotation {
+            value: s.to_string(),
+            pos,
+        };
+
+        let mut pos = 0;
+        let piece = match s.chars().next() {
+            Some(c) if c.is_ascii_uppercase() => {
+                pos += 1;
+                Some(PieceType::from_short_form(&c).map_err(|_| bad(0))?)
+            }
+            _ => None,
+        };
+
+        let from = s.get(pos..pos + 2).and_then(parse_square).ok_or(bad(pos))?;
+        pos += 2;
+
+        let mut annotations = Annotations::default();
+        let mut annotated = true;
+        match s.as_bytes().get(pos) {
+            Some(b'-') => pos += 1,
+            Some(b'x') => {
+                annotations.capture = true;
+                pos += 1;
+            }
+            _ => annotated = false,
+        }
+
+        let to = s.get(pos..pos + 2).and_then(parse_square).ok_or(bad(pos))?;
+        pos += 2;
+
+        if s.as_bytes().get(pos) == Some(&b'*') {
+            annotations.tower = true;
+            pos += 1;
+        }
+        for (offset, c) in s[pos..].char_indices() {
+            if c != '+' {
+                return Err(bad(pos + offset));
+            }
+            annotations.points = annotations.points.saturating_add(1);
+        }
+        annotated |= annotations.tower || annotations.points > 0;
+
+        Ok(ParsedMove {
+            piece,
+            from,
+            to,
+            annotated,
+            annotations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Board, Piece, Team};
+    use rand::prelude::SliceRandom;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro128Plus;
+
+    #[test]
+    fn test_squares() {
+        assert_eq!(square_name(0), "a1");
+        assert_eq!(square_name(63), "h8");
+        for pos in 0..64 {
+            assert_eq!(parse_square(&square_name(pos)), Some(pos));
+        }
+        assert_eq!(parse_square("i1"), None);
+        assert_eq!(parse_square("a9"), None);
+        assert_eq!(parse_square("a"), None);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let mut board = Board::empty();
+        board.set_piece(0, Piece::new(PieceType::Robbe, Team::ONE, false));
+        board.set_piece(10, Piece::new(PieceType::Moewe, Team::TWO, false));
+        board.set_piece(54, Piece::new(PieceType::Seestern, Team::ONE, false));
+        board.set_piece(63, Piece::new(PieceType::Herzmuschel, Team::TWO, true));
+        let state = Gamestate::new(board);
+
+        let cases = [
+            ("Ra1-b3", 0, 17, PieceType::Robbe),
+            ("Ra1xc2*", 0, 10, PieceType::Robbe),
+            ("Sg7-g8+", 54, 62, PieceType::Seestern),
+            ("Sg7xh8++", 54, 63, PieceType::Seestern),
+        ];
+        for (notation, from, to, piece) in cases {
+            let mov = Move { from, to, piece };
+            assert_eq!(mov.notation(&state), notation);
+            assert_eq!(Move::from_notation(notation, &state), Ok(mov));
+        }
+
+        let capture = Move {
+            from: 0,
+            to: 10,
+            piece: PieceType::Robbe,
+        };
+        assert_eq!(Move::from_notation("a1c2", &state), Ok(capture));
+        for notation in ["a1-c2", "Ra1xc2", "a1c2*+"] {
+            assert_eq!(
+                Move::from_notation(notation, &state),
+                Err(GameError::NotationMismatch {
+                    notation: notation.to_string(),
+                    expected: "Ra1xc2*".to_string()
+                })
+            );
+        }
+        let scoring = Move {
+            from: 54,
+            to: 62,
+            piece: PieceType::Seestern,
+        };
+        assert_eq!(Move::from_notation("g7g8", &state), Ok(scoring));
+        assert!(Move::from_notation("Sg7-g8", &state).is_err());
+        assert!(matches!(
+            Move::from_notation("Ra1-a2", &state),
+            Err(GameError::IllegalMove(MoveError::Unreachable { .. }))
+        ));
+        assert!(matches!(
+            Move::from_notation("Ra1-b3#", &state),
+            Err(GameError::BadNotation { pos: 6, .. })
+        ));
+        assert!(matches!(
+            Move::from_notation("Xa1-b3", &state),
+            Err(GameError::BadNotation { pos: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_notation_round_trip() {
+        let mut rng = Xoshiro128Plus::seed_from_u64(5);
+        let mut state = Gamestate::new_random(&mut rng);
+
+        while !state.game_over() {
+            for mov in state.available_moves_current_player().iter() {
+                let notation = mov.notation(&state);
+                assert_eq!(Move::from_notation(&notation, &state), Ok(*mov));
+                assert_eq!(notation.parse::<Move>(), Ok(*mov));
+                assert_eq!(mov.to_string().parse::<Move>(), Ok(*mov));
+            }
+            let moves = state.available_moves_current_player();
+            state.apply_move(moves.choose(&mut rng).unwrap());
+        }
+    }
+}
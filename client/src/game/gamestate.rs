@@ -1,9 +1,13 @@
-use crate::game::{zobrist, Board, Fen, IGamestate, Move, Team, Tile};
+use crate::game::{lookup_moves, zobrist, Board, Fen, IGamestate, Move, MoveError, Team, Tile};
 use rand::Rng;
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use thincollections::thin_vec::ThinVec;
 
+/// The most ambers a team can hold in a gamestate, as the game ends once a team has two
+/// of them and the Zobrist hash has no keys beyond this. Moves scoring more are capped.
+pub const MAX_AMBERS: u8 = 3;
+
 #[derive(Debug, Copy, Clone)]
 pub struct Gamestate {
     pub board: Board,
@@ -39,6 +43,22 @@ impl Gamestate {
         Self::new(board)
     }
 
+    /// Returns: the gamestate with the teams swapping sides, see [`Board::flipped`], and
+    /// their ambers. The other team moves in the same round, so the turn changes by one,
+    /// which may let the game end one turn earlier or later than the original.
+    pub fn flipped(&self) -> Self {
+        Self::new_with(
+            self.board.flipped(),
+            self.turn ^ 1,
+            [self.ambers[1], self.ambers[0]],
+        )
+    }
+
+    /// Returns: the gamestate with the board mirrored horizontally, see [`Board::mirrored`]
+    pub fn mirrored(&self) -> Self {
+        Self::new_with(self.board.mirrored(), self.turn, self.ambers)
+    }
+
     /// Calculates the winner of the match. <br>
     /// Returns: None, if the game isn't over or is a true tie
     #[inline]
@@ -79,6 +99,76 @@ impl Gamestate {
         None
     }
 
+    /// Checks whether the given move may be played by the current player.
+    /// Returns: the reason for the move being illegal, if it is
+    pub fn validate_move(&self, game_move: &Move) -> Result<(), MoveError> {
+        if self.game_over() {
+            return Err(MoveError::GameOver);
+        }
+        for pos in [game_move.from, game_move.to] {
+            if pos >= 64 {
+                return Err(MoveError::OutOfBounds(pos));
+            }
+        }
+
+        let piece = self
+            .board
+            .piece_at(game_move.from)
+            .ok_or(MoveError::NoPieceAt(game_move.from))?;
+        let team = self.current_player();
+        if piece.team != team {
+            return Err(MoveError::WrongTeam {
+                pos: game_move.from,
+                expected: team,
+                found: piece.team,
+            });
+        }
+        if piece.piece_type != game_move.piece {
+            return Err(MoveError::WrongPieceType {
+                pos: game_move.from,
+                claimed: game_move.piece,
+                found: piece.piece_type,
+            });
+        }
+        if !lookup_moves(piece.piece_type, game_move.from, team).get_bit(game_move.to) {
+            return Err(MoveError::Unreachable {
+                from: game_move.from,
+                to: game_move.to,
+                piece: piece.piece_type,
+            });
+        }
+        if self.board.player_pieces(team).get_bit(game_move.to) {
+            return Err(MoveError::OwnPieceOnTarget(game_move.to));
+        }
+        Ok(())
+    }
+
+    /// Returns: whether the current player may play the given move
+    #[inline]
+    pub fn is_legal(&self, game_move: &Move) -> bool {
+        self.validate_move(game_move).is_ok()
+    }
+
+    /// Applies the move only if it is legal, leaving the gamestate untouched otherwise.
+    pub fn try_apply_move(&mut self, game_move: &Move) -> Result<(), MoveError> {
+        self.validate_move(game_move)?;
+        self.apply_move(game_move);
+        Ok(())
+    }
+
+    /// Searches the legal move of the current player which turns this gamestate into `next`.
+    /// Returns: None, if `next` can't be reached with a single legal move
+    pub fn move_leading_to(&self, next: &Gamestate) -> Option<Move> {
+        if self.game_over() {
+            return None;
+        }
+        self.available_moves_current_player().into_iter().find(|m| {
+            let mut child = *self;
+            child.apply_move(m);
+            child.turn == next.turn && child.ambers == next.ambers && child.board == next.board
+        })
+    }
+
     /// Recalculates the Zobrist hash, discarding the previous hash information.
     #[inline]
     pub fn recalculate_hash(&mut self) -> u64 {
@@ -141,7 +231,9 @@ impl IGamestate for Gamestate {
         /////////////////////////////////////////////////////////
 
         let points = self.board.apply_move(game_move, self.current_player()); //Apply the move to the board, return the points gotten by jumping on other pieces
-        self.ambers[self.current_player() as usize] += points; //add points
+        // A loaded position may already hold MAX_AMBERS ambers, which the hash can't go beyond
+        let ambers = &mut self.ambers[self.current_player() as usize];
+        *ambers = (*ambers + points).min(MAX_AMBERS); //add points
         self.turn += 1; //Next round
 
         ///////////////// AFTER APPLY MOVE!!! ///////////////////
@@ -168,52 +260,198 @@ impl Display for Gamestate {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::game::PieceType;
+    use crate::game::{Piece, PieceType};
+    use rand::prelude::SliceRandom;
     use rand::SeedableRng;
     use rand_xoshiro::Xoshiro128Plus;
 
     #[test]
     fn test_points_system() {
-        let mut rng = Xoshiro128Plus::seed_from_u64(2);
-        let board = Board::new_random(&mut rng);
-        let mut gamestate = Gamestate::new(board);
+        // A red Herzmuschel tower on b7, a blue Moewe on c8 and a blue Herzmuschel on g2
+        let mut gamestate = Gamestate::load_fen("v1 2m5/1H*6/8/8/8/8/6h1/8 r 0 0/0").unwrap();
 
+        // Capturing with a tower removes both pieces, reaching the finish line scores too
         let m = Move {
-            from: 0,
-            to: 6,
+            from: 49,
+            to: 58,
             piece: PieceType::Herzmuschel,
         };
-        gamestate.board.double.set_bit(0);
         gamestate.apply_move(&m);
-        //println!("{}", gamestate.board);
-        //println!("{:?}", gamestate.ambers);
+        assert_eq!(gamestate.ambers, [2, 0]);
+        assert_eq!(gamestate.board.piece_at(58), None);
+        assert_eq!(gamestate.board.validate(), Ok(()));
 
         let m = Move {
-            from: 6,
-            to: 7,
+            from: 14,
+            to: 5,
             piece: PieceType::Herzmuschel,
         };
         gamestate.apply_move(&m);
-        //println!("{}", gamestate.board);
-        //println!("{:?}", gamestate.ambers);
+        assert_eq!(gamestate.ambers, [2, 1]);
+        assert_eq!(gamestate.board, Board::empty());
+        assert!(gamestate.game_over());
+        assert_eq!(gamestate.winner(), Some(Team::ONE));
+    }
 
-        let m = Move {
-            from: 63,
-            to: 17,
-            piece: PieceType::Herzmuschel,
-        };
-        gamestate.apply_move(&m);
-        //println!("{}", gamestate.board);
-        //println!("{:?}", gamestate.ambers);
+    fn validation_state() -> Gamestate {
+        let mut board = Board::empty();
+        board.set_piece(0, Piece::new(PieceType::Robbe, Team::ONE, false));
+        board.set_piece(17, Piece::new(PieceType::Moewe, Team::ONE, false));
+        board.set_piece(63, Piece::new(PieceType::Seestern, Team::TWO, false));
+        Gamestate::new(board)
+    }
+
+    #[test]
+    fn test_generated_moves_are_legal() {
+        let mut rng = Xoshiro128Plus::seed_from_u64(7);
+        let mut gamestate = Gamestate::new_random(&mut rng);
+
+        while !gamestate.game_over() {
+            let moves = gamestate.available_moves_current_player();
+            for m in moves.iter() {
+                assert_eq!(gamestate.validate_move(m), Ok(()));
+            }
+            gamestate.apply_move(moves.choose(&mut rng).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_illegal_moves() {
+        let gamestate = validation_state();
+        let mov = |from, to, piece| Move { from, to, piece };
+
+        assert!(gamestate.is_legal(&mov(0, 10, PieceType::Robbe)));
+        assert_eq!(
+            gamestate.validate_move(&mov(20, 28, PieceType::Moewe)),
+            Err(MoveError::NoPieceAt(20))
+        );
+        assert_eq!(
+            gamestate.validate_move(&mov(63, 54, PieceType::Seestern)),
+            Err(MoveError::WrongTeam {
+                pos: 63,
+                expected: Team::ONE,
+                found: Team::TWO
+            })
+        );
+        assert_eq!(
+            gamestate.validate_move(&mov(0, 10, PieceType::Moewe)),
+            Err(MoveError::WrongPieceType {
+                pos: 0,
+                claimed: PieceType::Moewe,
+                found: PieceType::Robbe
+            })
+        );
+        assert_eq!(
+            gamestate.validate_move(&mov(0, 1, PieceType::Robbe)),
+            Err(MoveError::Unreachable {
+                from: 0,
+                to: 1,
+                piece: PieceType::Robbe
+            })
+        );
+        assert_eq!(
+            gamestate.validate_move(&mov(0, 17, PieceType::Robbe)),
+            Err(MoveError::OwnPieceOnTarget(17))
+        );
+        assert_eq!(
+            gamestate.validate_move(&mov(0, 64, PieceType::Robbe)),
+            Err(MoveError::OutOfBounds(64))
+        );
+
+        let finished = Gamestate::new_with(gamestate.board, 60, [0, 0]);
+        assert_eq!(
+            finished.validate_move(&mov(0, 10, PieceType::Robbe)),
+            Err(MoveError::GameOver)
+        );
+    }
+
+    #[test]
+    fn test_try_apply_move() {
+        let mut gamestate = validation_state();
+        let before = gamestate;
+
+        assert!(gamestate
+            .try_apply_move(&Move {
+                from: 0,
+                to: 17,
+                piece: PieceType::Robbe,
+            })
+            .is_err());
+        assert_eq!(gamestate, before);
+        assert_eq!(gamestate.turn, before.turn);
 
         let m = Move {
-            from: 17,
-            to: 16,
-            piece: PieceType::Herzmuschel,
+            from: 0,
+            to: 10,
+            piece: PieceType::Robbe,
         };
-        gamestate.apply_move(&m);
-        //println!("{}", gamestate.board);
-        //println!("{:?}", gamestate.ambers);
+        assert!(gamestate.try_apply_move(&m).is_ok());
+        assert_eq!(gamestate.turn, 1);
+        assert!(gamestate.board.robben.get_bit(10));
+
+        assert_eq!(before.move_leading_to(&gamestate), Some(m));
+        assert!(gamestate.move_leading_to(&before).is_none());
+    }
+
+    fn sorted_moves(state: &Gamestate, transform: fn(&Move) -> Move) -> Vec<Move> {
+        let mut moves: Vec<Move> = state
+            .available_moves_current_player()
+            .iter()
+            .map(transform)
+            .collect();
+        moves.sort_by_key(|m| (m.from, m.to, m.piece as u8));
+        moves
+    }
+
+    #[test]
+    fn test_flipped_and_mirrored() {
+        let mut rng = Xoshiro128Plus::seed_from_u64(49);
+        for _ in 0..20 {
+            let mut gamestate = Gamestate::new_random(&mut rng);
+            // Starting positions are the same for both teams
+            assert_eq!(gamestate.board.flipped(), gamestate.board);
+
+            while !gamestate.game_over() {
+                let flipped = gamestate.flipped();
+                let mirrored = gamestate.mirrored();
+                assert_eq!(
+                    flipped.current_player(),
+                    gamestate.current_player().opponent()
+                );
+                assert_eq!(flipped.flipped().board, gamestate.board);
+                assert_eq!(flipped.flipped().turn, gamestate.turn);
+                assert_eq!(flipped.flipped().ambers, gamestate.ambers);
+                assert_eq!(mirrored.mirrored().board, gamestate.board);
+
+                // Every move has its counterpart on the transformed boards
+                assert_eq!(
+                    sorted_moves(&flipped, |m| *m),
+                    sorted_moves(&gamestate, Move::flipped)
+                );
+                assert_eq!(
+                    sorted_moves(&mirrored, |m| *m),
+                    sorted_moves(&gamestate, Move::mirrored)
+                );
+                for m in gamestate.available_moves_current_player() {
+                    let mut child = gamestate;
+                    child.apply_move(&m);
+                    let mut flipped_child = flipped;
+                    flipped_child.apply_move(&m.flipped());
+                    assert_eq!(flipped_child.board, child.board.flipped());
+                    assert_eq!(flipped_child.ambers, [child.ambers[1], child.ambers[0]]);
+                    let mut mirrored_child = mirrored;
+                    mirrored_child.apply_move(&m.mirrored());
+                    assert_eq!(mirrored_child.board, child.board.mirrored());
+                    assert_eq!(mirrored_child.ambers, child.ambers);
+                }
+
+                let moves = gamestate.available_moves_current_player();
+                match moves.choose(&mut rng) {
+                    Some(m) => gamestate.apply_move(m),
+                    None => break,
+                }
+            }
+        }
     }
 }
 
@@ -227,4 +465,4 @@ impl PartialEq for Gamestate {
     fn eq(&self, other: &Self) -> bool {
         self.hash == other.hash
     }
-}
\ No newline at end of file
+}
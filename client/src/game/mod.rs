@@ -1,11 +1,16 @@
 mod bitboard;
 mod board;
+mod error;
 mod fen;
 mod game_move;
 mod gamestate;
 mod move_generation;
 mod piece;
 mod piece_type;
+#[cfg(any(test, feature = "reference"))]
+mod reference;
+#[cfg(feature = "game-serde")]
+mod serialization;
 mod short_form;
 mod team;
 mod traits;
@@ -13,12 +18,17 @@ mod zobrist;
 
 pub use bitboard::*;
 pub use board::*;
+pub use error::*;
 pub use fen::*;
 pub use game_move::*;
 pub use gamestate::*;
 pub use move_generation::*;
 pub use piece::*;
 pub use piece_type::*;
+#[cfg(any(test, feature = "reference"))]
+pub use reference::*;
+#[cfg(feature = "game-serde")]
+pub use serialization::*;
 pub use short_form::*;
 pub use team::*;
 pub use traits::*;
@@ -5,7 +5,7 @@ Current: Layout II
 */
 
 use crate::bitboard;
-use crate::game::{Bitboard, Team};
+use crate::game::{Bitboard, PieceType, Team};
 
 const NOT_FILE_A: u64 = !0x0101010101010101;
 const NOT_FILE_B: u64 = !0x0202020202020202;
@@ -512,6 +512,18 @@ pub fn seestern_lookup_moves(pos: u8, player: Team) -> Bitboard {
     bitboard!(*unsafe { LOOKUP_SEESTERN.get_unchecked((((player as u8) << 6) + pos) as usize) })
 }
 
+/// Returns: the squares a piece of the given type and team could move to from `pos`,
+/// regardless of the other pieces on the board.
+#[inline]
+pub fn lookup_moves(piece_type: PieceType, pos: u8, player: Team) -> Bitboard {
+    match piece_type {
+        PieceType::Robbe => robbe_lookup_moves(pos),
+        PieceType::Moewe => moewe_lookup_moves(pos),
+        PieceType::Seestern => seestern_lookup_moves(pos, player),
+        PieceType::Herzmuschel => muschel_lookup_moves(pos, player),
+    }
+}
+
 //------------------------------------------------------------------------------------------------//
 
 #[cfg(test)]
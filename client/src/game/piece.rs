@@ -1,8 +1,8 @@
-use crate::game::{PieceType, ShortForm, Team};
-use socha_client_2022::util::{SCError, SCResult};
+use crate::game::{GameError, PieceType, ShortForm, Team};
 
 /// A placeable figure on the board.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "game-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Piece {
     /// Type of the (topmost) piece.
     pub(crate) piece_type: PieceType,
@@ -22,10 +22,25 @@ impl Piece {
             stacked,
         }
     }
+
+    #[inline]
+    pub fn piece_type(&self) -> PieceType {
+        self.piece_type
+    }
+
+    #[inline]
+    pub fn team(&self) -> Team {
+        self.team
+    }
+
+    #[inline]
+    pub fn is_stacked(&self) -> bool {
+        self.stacked
+    }
 }
 
 impl ShortForm<String> for Piece {
-    type Err = SCError;
+    type Err = GameError;
 
     fn to_short_form(&self) -> String {
         let piece = if self.team == Team::ONE {
@@ -40,14 +55,25 @@ impl ShortForm<String> for Piece {
         out
     }
 
-    fn from_short_form(s: &String) -> SCResult<Self> {
-        let piece_type = PieceType::from_short_form(&s.chars().nth(0).unwrap())?;
-        let team = if s.chars().nth(0).unwrap().is_uppercase() {
+    fn from_short_form(s: &String) -> Result<Self, GameError> {
+        let unknown = || GameError::UnknownShortForm(s.clone());
+
+        let mut chars = s.chars();
+        let first = chars.next().ok_or_else(unknown)?;
+        let piece_type = PieceType::from_short_form(&first).map_err(|_| unknown())?;
+        let team = if first.is_uppercase() {
             Team::ONE
         } else {
             Team::TWO
         };
-        let stacked = s.chars().nth(1) == Some('*');
+        let stacked = match chars.next() {
+            None => false,
+            Some('*') => true,
+            Some(_) => return Err(unknown()),
+        };
+        if chars.next().is_some() {
+            return Err(unknown());
+        }
         Ok(Piece {
             piece_type,
             team,
@@ -55,3 +81,22 @@ impl ShortForm<String> for Piece {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_form() {
+        for s in ["R", "r*", "M*", "s", "h"] {
+            let piece = Piece::from_short_form(&s.to_string()).unwrap();
+            assert_eq!(piece.to_short_form(), s);
+        }
+        for s in ["", "x", "R**", "*", "Rr"] {
+            assert_eq!(
+                Piece::from_short_form(&s.to_string()),
+                Err(GameError::UnknownShortForm(s.to_string()))
+            );
+        }
+    }
+}
@@ -1,10 +1,10 @@
 use std::fmt;
 use std::str::FromStr;
 
-use crate::game::ShortForm;
-use socha_client_2022::util::{SCError, SCResult};
+use crate::game::{GameError, ShortForm};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "game-serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceType {
     /// Moves only diagonally forwards.
     Herzmuschel = 0,
@@ -38,21 +38,24 @@ impl fmt::Display for PieceType {
 
 //noinspection DuplicatedCode
 impl FromStr for PieceType {
-    type Err = SCError;
+    type Err = GameError;
 
-    fn from_str(s: &str) -> SCResult<Self> {
+    fn from_str(s: &str) -> Result<Self, GameError> {
         match s {
             "Herzmuschel" => Ok(Self::Herzmuschel),
             "Moewe" => Ok(Self::Moewe),
             "Seestern" => Ok(Self::Seestern),
             "Robbe" => Ok(Self::Robbe),
-            _ => Err(SCError::UnknownVariant(format!("Unknown piece type {}", s))),
+            _ => Err(GameError::UnknownVariant(format!(
+                "Unknown piece type {}",
+                s
+            ))),
         }
     }
 }
 
 impl ShortForm<char> for PieceType {
-    type Err = SCError;
+    type Err = GameError;
 
     fn to_short_form(&self) -> char {
         match self {
@@ -63,13 +66,13 @@ impl ShortForm<char> for PieceType {
         }
     }
 
-    fn from_short_form(c: &char) -> SCResult<Self> {
+    fn from_short_form(c: &char) -> Result<Self, GameError> {
         match c.to_ascii_lowercase() {
             'h' => Ok(Self::Herzmuschel),
             'm' => Ok(Self::Moewe),
             's' => Ok(Self::Seestern),
             'r' => Ok(Self::Robbe),
-            _ => Err(SCError::UnknownVariant(format!("Unknown short form {}", c))),
+            _ => Err(GameError::UnknownShortForm(c.to_string())),
         }
     }
 }
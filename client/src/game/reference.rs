@@ -0,0 +1,309 @@
+//! A deliberately naive implementation of the rules on a grid of squares, written for
+//! clarity instead of speed. It shares nothing with the bitboards of [`Board`] besides
+//! the types of pieces and moves, so [`compare_random_game`] can check every
+//! optimization of the move generation against it.
+
+use std::fmt;
+
+use rand::Rng;
+
+use crate::game::{
+    square_name, Fen, Gamestate, IGamestate, Move, Piece, PieceType, Team, MAX_AMBERS,
+};
+
+/// The squares a piece may move to, as steps of (file, rank) for team ONE, which moves
+/// towards higher ranks. Team TWO mirrors the ranks.
+fn steps(piece_type: PieceType) -> &'static [(i8, i8)] {
+    match piece_type {
+        PieceType::Herzmuschel => &[(-1, 1), (1, 1)],
+        PieceType::Moewe => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+        PieceType::Seestern => &[(-1, 1), (0, 1), (1, 1), (-1, -1), (1, -1)],
+        PieceType::Robbe => &[
+            (1, 2),
+            (2, 1),
+            (2, -1),
+            (1, -2),
+            (-1, -2),
+            (-2, -1),
+            (-2, 1),
+            (-1, 2),
+        ],
+    }
+}
+
+/// Returns: the rank on which the light pieces of the team leave the board
+fn finish_rank(team: Team) -> usize {
+    match team {
+        Team::ONE => 7,
+        Team::TWO => 0,
+    }
+}
+
+/// A gamestate of the reference rules, with the board as a grid indexed by rank and file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceState {
+    pub squares: [[Option<Piece>; 8]; 8],
+    pub turn: u8,
+    pub ambers: [u8; 2],
+}
+
+impl ReferenceState {
+    pub fn from_gamestate(state: &Gamestate) -> Self {
+        let mut squares = [[None; 8]; 8];
+        for (rank, row) in squares.iter_mut().enumerate() {
+            for (file, square) in row.iter_mut().enumerate() {
+                *square = state.board.piece_at((rank * 8 + file) as u8);
+            }
+        }
+        Self {
+            squares,
+            turn: state.turn,
+            ambers: state.ambers,
+        }
+    }
+
+    pub fn current_player(&self) -> Team {
+        match self.turn % 2 {
+            0 => Team::ONE,
+            _ => Team::TWO,
+        }
+    }
+
+    /// The game ends after 60 turns, or after a round in which a team reached 2 ambers.
+    pub fn game_over(&self) -> bool {
+        let round_complete = self.current_player() == Team::ONE;
+        self.turn >= 60 || (round_complete && self.ambers.iter().any(|&ambers| ambers >= 2))
+    }
+
+    pub fn piece_at(&self, file: usize, rank: usize) -> Option<Piece> {
+        self.squares[rank][file]
+    }
+
+    /// Returns: the moves of the current player, in no particular order
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let team = self.current_player();
+        let forward = match team {
+            Team::ONE => 1,
+            Team::TWO => -1,
+        };
+        let mut moves = vec![];
+        for rank in 0..8 {
+            for file in 0..8 {
+                let piece = match self.piece_at(file, rank) {
+                    Some(piece) if piece.team == team => piece,
+                    _ => continue,
+                };
+                for (file_step, rank_step) in steps(piece.piece_type) {
+                    let to_file = file as i8 + file_step;
+                    let to_rank = rank as i8 + rank_step * forward;
+                    if !(0..8).contains(&to_file) || !(0..8).contains(&to_rank) {
+                        continue;
+                    }
+                    let (to_file, to_rank) = (to_file as usize, to_rank as usize);
+                    match self.piece_at(to_file, to_rank) {
+                        Some(other) if other.team == team => continue,
+                        _ => moves.push(Move {
+                            from: (rank * 8 + file) as u8,
+                            to: (to_rank * 8 + to_file) as u8,
+                            piece: piece.piece_type,
+                        }),
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    /// Plays a legal move of the current player:
+    /// - a piece moving onto an enemy piece captures it, forming a tower with it;
+    /// - if either of them already was a tower, both are removed instead and the mover
+    ///   scores an amber;
+    /// - a light piece reaching the enemy's baseline leaves the board and scores an
+    ///   amber, in addition to any amber scored by the capture on the same square.
+    pub fn apply_move(&mut self, mov: &Move) {
+        let team = self.current_player();
+        let (from_file, from_rank) = ((mov.from % 8) as usize, (mov.from / 8) as usize);
+        let (to_file, to_rank) = ((mov.to % 8) as usize, (mov.to / 8) as usize);
+        let mut piece = self.squares[from_rank][from_file]
+            .take()
+            .expect("The move starts on an empty square");
+        let mut ambers = 0;
+
+        let mut on_board = true;
+        if let Some(captured) = self.squares[to_rank][to_file].take() {
+            if piece.stacked || captured.stacked {
+                on_board = false;
+                ambers += 1;
+            } else {
+                piece.stacked = true;
+            }
+        }
+        if piece.piece_type.is_light() && to_rank == finish_rank(team) {
+            on_board = false;
+            ambers += 1;
+        }
+        if on_board {
+            self.squares[to_rank][to_file] = Some(piece);
+        }
+
+        // Like Gamestate, which can't count more ambers
+        self.ambers[team as usize] = (self.ambers[team as usize] + ambers).min(MAX_AMBERS);
+        self.turn += 1;
+    }
+
+    /// Returns: the first difference to the given gamestate, if any
+    pub fn difference_to(&self, state: &Gamestate) -> Option<String> {
+        if self.turn != state.turn {
+            return Some(format!("turn {} instead of {}", state.turn, self.turn));
+        }
+        if self.ambers != state.ambers {
+            return Some(format!(
+                "ambers {:?} instead of {:?}",
+                state.ambers, self.ambers
+            ));
+        }
+        for rank in 0..8 {
+            for file in 0..8 {
+                let pos = (rank * 8 + file) as u8;
+                let expected = self.piece_at(file, rank);
+                let found = state.board.piece_at(pos);
+                if expected != found {
+                    return Some(format!(
+                        "{:?} instead of {:?} on {}",
+                        found,
+                        expected,
+                        square_name(pos)
+                    ));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A difference between [`Gamestate`] and the reference rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// The position, in which both still agreed
+    pub state: Gamestate,
+    /// The move leading to the difference, none if the legal moves differ
+    pub mov: Option<Move>,
+    pub message: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mov {
+            Some(mov) => write!(
+                f,
+                "After {} in {}: {}",
+                mov,
+                self.state.to_fen(),
+                self.message
+            ),
+            None => write!(f, "In {}: {}", self.state.to_fen(), self.message),
+        }
+    }
+}
+
+fn sorted(mut moves: Vec<Move>) -> Vec<Move> {
+    moves.sort_by_key(|mov| (mov.from, mov.to, mov.piece as u8));
+    moves
+}
+
+/// Plays a random game from the given position, checking after every move that the
+/// legal moves and the resulting position agree with the reference rules.
+/// Returns: the number of plies played, or the first divergence
+pub fn compare_random_game<R: Rng>(start: Gamestate, rng: &mut R) -> Result<u32, Divergence> {
+    let mut state = start;
+    let mut reference = ReferenceState::from_gamestate(&start);
+    let mut plies = 0;
+    loop {
+        let diverged = |state: Gamestate, mov, message| Divergence {
+            state,
+            mov,
+            message,
+        };
+        if state.game_over() != reference.game_over() {
+            let message = format!("game over is {}", state.game_over());
+            return Err(diverged(state, None, message));
+        }
+        if state.game_over() {
+            return Ok(plies);
+        }
+        let moves = sorted(state.available_moves_current_player().to_vec());
+        let expected = sorted(reference.legal_moves());
+        if moves != expected {
+            let missing: Vec<String> = expected
+                .iter()
+                .filter(|mov| !moves.contains(mov))
+                .map(|mov| mov.to_string())
+                .collect();
+            let extra: Vec<String> = moves
+                .iter()
+                .filter(|mov| !expected.contains(mov))
+                .map(|mov| mov.to_string())
+                .collect();
+            let message = format!("missing moves {:?}, extra moves {:?}", missing, extra);
+            return Err(diverged(state, None, message));
+        }
+        if moves.is_empty() {
+            return Ok(plies);
+        }
+
+        let mov = moves[rng.gen_range(0..moves.len())];
+        let before = state;
+        state.apply_move(&mov);
+        reference.apply_move(&mov);
+        plies += 1;
+        if let Some(message) = reference.difference_to(&state) {
+            return Err(diverged(before, Some(mov), message));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_random_games() {
+        let mut rng = StdRng::seed_from_u64(46);
+        for _ in 0..300 {
+            let start = Gamestate::new_random(&mut rng);
+            if let Err(divergence) = compare_random_game(start, &mut rng) {
+                panic!("{}", divergence);
+            }
+        }
+    }
+
+    #[test]
+    fn test_towers() {
+        // A red Robbe captures a blue tower on c3, a red Moewe forms a tower on d8 and
+        // leaves the board for the finish line
+        let state = Gamestate::load_fen("v1 3m4/3M4/8/8/8/2s*5/8/1R6 r 0 0/0").unwrap();
+        let mut reference = ReferenceState::from_gamestate(&state);
+        reference.apply_move(&Move {
+            from: 1,
+            to: 18,
+            piece: PieceType::Robbe,
+        });
+        assert_eq!(reference.ambers, [1, 0]);
+        assert_eq!(reference.piece_at(2, 2), None);
+        assert_eq!(reference.piece_at(1, 0), None);
+
+        reference.turn += 1;
+        reference.apply_move(&Move {
+            from: 51,
+            to: 59,
+            piece: PieceType::Moewe,
+        });
+        assert_eq!(reference.ambers, [2, 0]);
+        assert_eq!(reference.piece_at(3, 7), None);
+        assert!(!reference.game_over());
+        reference.turn += 1;
+        assert!(reference.game_over());
+    }
+}
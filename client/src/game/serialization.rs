@@ -0,0 +1,346 @@
+//! Serde support for the game types, enabled by the `game-serde` feature.
+//!
+//! By default the types serialize compactly: bitboards as 16 hex digits, boards as their
+//! bitboards and gamestates as board, turn and ambers. Their human readable forms, FENs
+//! for boards and gamestates, notations for moves and short forms for pieces, are used
+//! with `#[serde(with = "client::game::human")]` on a field, or by wrapping values in
+//! [`Human`], e.g. for a list of moves.
+
+use std::fmt;
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{
+    parse_board, pieces_to_fen, Bitboard, Board, Fen, GameError, Gamestate, Move, Piece, PieceType,
+    ShortForm, Team, MAX_AMBERS,
+};
+
+impl Serialize for Bitboard {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:016x}", self.bits))
+    }
+}
+
+impl<'de> Deserialize<'de> for Bitboard {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let valid =
+            !hex.is_empty() && hex.len() <= 16 && hex.bytes().all(|b| b.is_ascii_hexdigit());
+        match u64::from_str_radix(&hex, 16) {
+            Ok(bits) if valid => Ok(Bitboard { bits }),
+            _ => Err(de::Error::invalid_value(
+                de::Unexpected::Str(&hex),
+                &"up to 16 hex digits",
+            )),
+        }
+    }
+}
+
+/// The fields of a [`Board`], which is validated when deserialized.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Board", deny_unknown_fields)]
+struct BoardFields {
+    red: Bitboard,
+    blue: Bitboard,
+    seesterne: Bitboard,
+    muscheln: Bitboard,
+    moewen: Bitboard,
+    robben: Bitboard,
+    double: Bitboard,
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BoardFields {
+            red: self.red,
+            blue: self.blue,
+            seesterne: self.seesterne,
+            muscheln: self.muscheln,
+            moewen: self.moewen,
+            robben: self.robben,
+            double: self.double,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = BoardFields::deserialize(deserializer)?;
+        let board = Board {
+            red: fields.red,
+            blue: fields.blue,
+            seesterne: fields.seesterne,
+            muscheln: fields.muscheln,
+            moewen: fields.moewen,
+            robben: fields.robben,
+            double: fields.double,
+        };
+        match board.validate() {
+            Ok(()) => Ok(board),
+            Err(violations) => {
+                let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                Err(de::Error::custom(format!(
+                    "Inconsistent board: {}",
+                    violations.join("; ")
+                )))
+            }
+        }
+    }
+}
+
+/// The fields of a [`Gamestate`], whose ambers are bounded like in a FEN and whose hash is
+/// calculated when deserialized.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Gamestate", deny_unknown_fields)]
+struct GamestateFields {
+    board: Board,
+    turn: u8,
+    ambers: [u8; 2],
+}
+
+impl Serialize for Gamestate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        GamestateFields {
+            board: self.board,
+            turn: self.turn,
+            ambers: self.ambers,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Gamestate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = GamestateFields::deserialize(deserializer)?;
+        if let Some(ambers) = fields.ambers.iter().find(|ambers| **ambers > MAX_AMBERS) {
+            return Err(de::Error::invalid_value(
+                de::Unexpected::Unsigned(*ambers as u64),
+                &"0 to 3 ambers",
+            ));
+        }
+        Ok(Gamestate::new_with(
+            fields.board,
+            fields.turn,
+            fields.ambers,
+        ))
+    }
+}
+
+/// A game type with a human readable form, see [`human`].
+pub trait HumanReadable: Sized {
+    fn to_human(&self) -> String;
+
+    fn from_human(s: &str) -> Result<Self, GameError>;
+}
+
+/// The extended FEN, see [`ExtendedFen`](crate::game::ExtendedFen).
+impl HumanReadable for Gamestate {
+    fn to_human(&self) -> String {
+        self.to_fen()
+    }
+
+    fn from_human(s: &str) -> Result<Self, GameError> {
+        Gamestate::load_fen(s)
+    }
+}
+
+/// The piece placement of a FEN, e.g. `hrrssmhm/8/8/8/8/8/8/MHMSSRRH`.
+impl HumanReadable for Board {
+    fn to_human(&self) -> String {
+        pieces_to_fen(self)
+    }
+
+    fn from_human(s: &str) -> Result<Self, GameError> {
+        parse_board(s, 0)
+    }
+}
+
+/// The notation without annotations, e.g. `Ra1-b3`, see [`Move`].
+impl HumanReadable for Move {
+    fn to_human(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_human(s: &str) -> Result<Self, GameError> {
+        s.parse()
+    }
+}
+
+/// The short form, e.g. `R` for a red Robbe or `m*` for a blue Moewe tower.
+impl HumanReadable for Piece {
+    fn to_human(&self) -> String {
+        self.to_short_form()
+    }
+
+    fn from_human(s: &str) -> Result<Self, GameError> {
+        Piece::from_short_form(&s.to_string())
+    }
+}
+
+impl HumanReadable for PieceType {
+    fn to_human(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_human(s: &str) -> Result<Self, GameError> {
+        s.parse()
+    }
+}
+
+impl HumanReadable for Team {
+    fn to_human(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_human(s: &str) -> Result<Self, GameError> {
+        s.parse()
+    }
+}
+
+/// Serializes any [`HumanReadable`] game type as a string in its human readable form,
+/// for use with `#[serde(with = "client::game::human")]`.
+pub mod human {
+    use super::*;
+
+    pub fn serialize<T: HumanReadable, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_human())
+    }
+
+    pub fn deserialize<'de, T: HumanReadable, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        T::from_human(&s).map_err(de::Error::custom)
+    }
+}
+
+/// Wraps a game type, so it is serialized in its human readable form, see [`human`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Human<T>(pub T);
+
+impl<T: HumanReadable> Serialize for Human<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        human::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de, T: HumanReadable> Deserialize<'de> for Human<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        human::deserialize(deserializer).map(Human)
+    }
+}
+
+impl<T: HumanReadable> fmt::Display for Human<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.to_human())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::IGamestate;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const FEN: &str = "v1 3m2r1/sm1hh2s/8/8/3S4/3HR3/2r*2M2/1R3M1S r 30 0/0";
+
+    #[test]
+    fn test_compact() {
+        let mut rng = StdRng::seed_from_u64(50);
+        let state = Gamestate::new_random(&mut rng);
+        let json = serde_json::to_string(&state).unwrap();
+        let read: Gamestate = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.board, state.board);
+        assert_eq!(read.turn, state.turn);
+        assert_eq!(read.hash, state.hash);
+
+        let board = serde_json::to_value(Board::empty()).unwrap();
+        assert_eq!(board["red"], "0000000000000000");
+        let json = serde_json::to_value(state).unwrap();
+        assert_eq!(json["board"]["red"], "00000000000000ff");
+        assert_eq!(json["board"]["blue"], "ff00000000000000");
+        assert_eq!(json["turn"], 0);
+
+        let mov = Move {
+            from: 1,
+            to: 18,
+            piece: PieceType::Robbe,
+        };
+        let json = serde_json::to_string(&mov).unwrap();
+        assert_eq!(json, r#"{"from":1,"to":18,"piece":"Robbe"}"#);
+        assert_eq!(serde_json::from_str::<Move>(&json).unwrap(), mov);
+
+        let piece = Piece::new(PieceType::Moewe, Team::TWO, true);
+        let json = serde_json::to_string(&piece).unwrap();
+        assert_eq!(serde_json::from_str::<Piece>(&json).unwrap(), piece);
+    }
+
+    #[test]
+    fn test_compact_errors() {
+        let mut board = serde_json::to_value(Board::empty()).unwrap();
+        board["double"] = "0000000000000001".into();
+        let err = serde_json::from_value::<Board>(board.clone()).unwrap_err();
+        assert!(err.to_string().contains("Towers on empty squares: a1"));
+
+        for bad in ["", "0x1", "+1", "10000000000000000", "g"] {
+            board["double"] = bad.into();
+            assert!(serde_json::from_value::<Board>(board.clone()).is_err());
+        }
+
+        // The same bounds as in a FEN
+        let mut state = serde_json::to_value(Gamestate::new(Board::empty())).unwrap();
+        state["ambers"] = serde_json::json!([0, 3]);
+        state["turn"] = 255.into();
+        assert!(serde_json::from_value::<Gamestate>(state.clone()).is_ok());
+        state["ambers"] = serde_json::json!([4, 0]);
+        let err = serde_json::from_value::<Gamestate>(state.clone()).unwrap_err();
+        assert!(err.to_string().contains("0 to 3 ambers"));
+        state["ambers"] = serde_json::json!([0, 0]);
+        state["turn"] = 256.into();
+        assert!(serde_json::from_value::<Gamestate>(state).is_err());
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Analysis {
+        #[serde(with = "human")]
+        state: Gamestate,
+        #[serde(with = "human")]
+        board: Board,
+        moves: Vec<Human<Move>>,
+        piece: Human<Piece>,
+    }
+
+    #[test]
+    fn test_human() {
+        let state = Gamestate::load_fen(FEN).unwrap();
+        let analysis = Analysis {
+            state,
+            board: state.board,
+            moves: state
+                .available_moves_current_player()
+                .iter()
+                .take(2)
+                .map(|m| Human(*m))
+                .collect(),
+            piece: Human(state.board.piece_at(10).unwrap()),
+        };
+        let json = serde_json::to_value(&analysis).unwrap();
+        assert_eq!(json["state"], FEN);
+        assert_eq!(json["board"], "3m2r1/sm1hh2s/8/8/3S4/3HR3/2r*2M2/1R3M1S");
+        assert_eq!(json["moves"][0], analysis.moves[0].to_string());
+        assert_eq!(json["piece"], "r*");
+        let read: Analysis = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(read, analysis);
+
+        let mut bad = json;
+        bad["state"] = "v1 8/8 r 0 0/0".into();
+        assert!(serde_json::from_value::<Analysis>(bad).is_err());
+    }
+}
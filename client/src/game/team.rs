@@ -1,11 +1,12 @@
 use std::fmt;
 use std::str::FromStr;
 
-use socha_client_2022::util::{SCError, SCResult};
+use crate::game::GameError;
 use std::ops::Not;
 
 /// A playing party in the game.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "game-serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Team {
     ONE = 0,
     TWO = 1,
@@ -58,13 +59,13 @@ impl fmt::Display for Team {
 
 //noinspection DuplicatedCode
 impl FromStr for Team {
-    type Err = SCError;
+    type Err = GameError;
 
-    fn from_str(s: &str) -> SCResult<Self> {
+    fn from_str(s: &str) -> Result<Self, GameError> {
         match s {
             "ONE" => Ok(Team::ONE),
             "TWO" => Ok(Team::TWO),
-            _ => Err(SCError::UnknownVariant(format!("Unknown team {}", s))),
+            _ => Err(GameError::UnknownVariant(format!("Unknown team {}", s))),
         }
     }
 }
@@ -1188,10 +1188,24 @@ pub fn hash_for_score(score: [u8; 2]) -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use crate::game::{Fen, Gamestate, IGamestate};
+    use crate::game::{Fen, Gamestate, IGamestate, Move, PieceType};
     use rand::prelude::SliceRandom;
     use rand::{thread_rng, RngCore};
 
+    #[test]
+    fn test_scores_beyond_table() {
+        // Blue can't have 3 ambers on its turn in a game, but it may in a loaded position
+        let mut state = Gamestate::load_fen("v1 8/8/8/8/8/8/1m6/1M*6 b 1 0/3").unwrap();
+        state.apply_move(&Move {
+            from: 9,
+            to: 1,
+            piece: PieceType::Moewe,
+        });
+        assert_eq!(state.ambers, [0, 3]);
+        let hash = state.hash;
+        assert_eq!(state.recalculate_hash(), hash);
+    }
+
     #[test]
     fn init_zobrist() {
         let mut piece_lookup: [[[[u64; 2]; 2]; 4]; 64] = [[[[0u64; 2]; 2]; 4]; 64];
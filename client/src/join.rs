@@ -0,0 +1,131 @@
+use std::error::Error;
+use std::fmt;
+
+/// How the client enters a game on the server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum JoinMode {
+    /// Any game, opening a new room if no other client waits for an opponent
+    #[default]
+    Any,
+    /// An open room, which already waits for a second player
+    Room(String),
+    /// A game prepared by the server's administrator, e.g. in a tournament
+    Reservation(String),
+}
+
+impl JoinMode {
+    /// Chooses the join mode from the given options, which may name at most one of
+    /// a reservation code and a room.
+    pub fn from_options(
+        reservation: Option<String>,
+        room: Option<String>,
+    ) -> Result<Self, JoinError> {
+        match (reservation, room) {
+            (Some(_), Some(_)) => Err(JoinError::ReservationAndRoom),
+            (Some(code), None) if code.trim().is_empty() => Err(JoinError::Empty("reservation")),
+            (None, Some(room)) if room.trim().is_empty() => Err(JoinError::Empty("room")),
+            (Some(code), None) => Ok(JoinMode::Reservation(code)),
+            (None, Some(room)) => Ok(JoinMode::Room(room)),
+            (None, None) => Ok(JoinMode::Any),
+        }
+    }
+
+    /// Checks that the join mode allows to play the given number of games in a row.
+    /// A room or reservation only stands for a single game.
+    pub fn check_games(&self, games: usize) -> Result<(), JoinError> {
+        match self {
+            _ if games == 0 => Err(JoinError::NoGames),
+            JoinMode::Any => Ok(()),
+            _ if games == 1 => Ok(()),
+            _ => Err(JoinError::SingleGame {
+                mode: self.clone(),
+                games,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for JoinMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinMode::Any => write!(f, "any game"),
+            JoinMode::Room(room) => write!(f, "room {}", room),
+            JoinMode::Reservation(code) => write!(f, "reservation {}", code),
+        }
+    }
+}
+
+/// Invalid combinations of the join options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JoinError {
+    /// A game can either be reserved or be joined by its room, not both.
+    ReservationAndRoom,
+    /// The named option is given, but empty.
+    Empty(&'static str),
+    /// A room or reservation is given together with more than one game.
+    SingleGame { mode: JoinMode, games: usize },
+    /// The client is asked to play no game at all.
+    NoGames,
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoinError::ReservationAndRoom => write!(
+                f,
+                "A reservation and a room can't be used together, a reservation already names its room"
+            ),
+            JoinError::Empty(option) => write!(f, "The {} must not be empty", option),
+            JoinError::SingleGame { mode, games } => write!(
+                f,
+                "Joining {} plays a single game, but {} games were requested",
+                mode, games
+            ),
+            JoinError::NoGames => write!(f, "At least one game has to be played"),
+        }
+    }
+}
+
+impl Error for JoinError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_options() {
+        let code = || Some("abc".to_string());
+        assert_eq!(JoinMode::from_options(None, None), Ok(JoinMode::Any));
+        assert_eq!(
+            JoinMode::from_options(code(), None),
+            Ok(JoinMode::Reservation("abc".to_string()))
+        );
+        assert_eq!(
+            JoinMode::from_options(None, code()),
+            Ok(JoinMode::Room("abc".to_string()))
+        );
+        assert_eq!(
+            JoinMode::from_options(code(), code()),
+            Err(JoinError::ReservationAndRoom)
+        );
+        assert_eq!(
+            JoinMode::from_options(None, Some(" ".to_string())),
+            Err(JoinError::Empty("room"))
+        );
+    }
+
+    #[test]
+    fn test_check_games() {
+        assert!(JoinMode::Any.check_games(10).is_ok());
+        assert_eq!(JoinMode::Any.check_games(0), Err(JoinError::NoGames));
+        let room = JoinMode::Room("abc".to_string());
+        assert!(room.check_games(1).is_ok());
+        assert_eq!(
+            room.check_games(2),
+            Err(JoinError::SingleGame {
+                mode: room.clone(),
+                games: 2
+            })
+        );
+    }
+}
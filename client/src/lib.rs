@@ -1,6 +1,13 @@
 pub mod algorithms;
 pub mod bridge;
 pub mod client;
+pub mod config;
 pub mod game;
+pub mod join;
+pub mod logging;
+pub mod record;
+pub mod telemetry;
+pub mod time_manager;
 //pub mod tables;
 pub mod utils;
+pub mod watchdog;
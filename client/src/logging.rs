@@ -0,0 +1,97 @@
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use chrono::Local;
+use env_logger::{Builder, Target};
+use log::{LevelFilter, Record};
+use serde::{Deserialize, Serialize};
+
+/// How log records are written to stdout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// `2022-04-01T12:00:00 [INFO] - message`, for reading along
+    #[default]
+    Text,
+    /// One JSON object per line with time, level, target and message, for log collectors
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format {}, expected text or json", s)),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// The environment variable, by which the logged modules are filtered further, in the
+/// syntax of `env_logger`, e.g. `client::algorithms=trace`.
+pub const LOG_FILTER_VAR: &str = "OSTSEESCHACH_LOG";
+
+/// Sets up the global logger, see [`LOG_FILTER_VAR`].
+pub fn init(level: LevelFilter, format: LogFormat) {
+    let mut builder = Builder::new();
+    builder.parse_env(LOG_FILTER_VAR);
+    match format {
+        LogFormat::Text => builder.format(|buf, record| {
+            writeln!(
+                buf,
+                "{} [{}] - {}",
+                Local::now().format("%Y-%m-%dT%H:%M:%S"),
+                record.level(),
+                record.args()
+            )
+        }),
+        LogFormat::Json => builder.format(|buf, record| writeln!(buf, "{}", to_json(record))),
+    };
+    builder.filter_level(level).target(Target::Stdout).init();
+}
+
+fn to_json(record: &Record) -> serde_json::Value {
+    serde_json::json!({
+        "time": Local::now().format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn test_json() {
+        let json = to_json(
+            &Record::builder()
+                .args(format_args!("Sending move: {}", "\"a1\""))
+                .level(Level::Warn)
+                .target("client::client")
+                .build(),
+        );
+        let line = json.to_string();
+        assert!(!line.contains('\n'));
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["level"], "WARN");
+        assert_eq!(parsed["target"], "client::client");
+        assert_eq!(parsed["message"], "Sending move: \"a1\"");
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
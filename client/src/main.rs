@@ -3,64 +3,203 @@
 pub mod algorithms;
 pub mod bridge;
 pub mod client;
+pub mod config;
 pub mod game;
+pub mod join;
+pub mod logging;
+pub mod record;
+pub mod telemetry;
+pub mod time_manager;
 pub mod utils;
+pub mod watchdog;
 
-use crate::algorithms::heuristics::*;
-use crate::algorithms::*;
-use crate::client::Client;
-use chrono::Local;
+use crate::algorithms::registry::{self, AlgorithmSettings};
+use crate::algorithms::Algorithm;
+use crate::client::{Client, SessionConfig};
+use crate::config::{
+    Config, ConfigError, ConfigLayer, ConnectionLayer, LoggingLayer, OutputLayer, TimeLayer,
+};
+use crate::logging::LogFormat;
 use clap::Parser;
-use env_logger::{Builder, Target};
 use log::LevelFilter;
 use std::env;
-use std::io::Write;
+use std::fmt::Display;
+use std::path::PathBuf;
 
 /// Rust client for the board game "Ostseeschach"
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// host of the game server
-    #[clap(short, long, default_value = "localhost")]
-    host: String,
+    /// host of the game server [default: localhost]
+    #[clap(short, long)]
+    host: Option<String>,
 
-    /// Port of the game server
-    #[clap(short, long, default_value_t = 13050)]
-    port: u16,
+    /// Port of the game server [default: 13050]
+    #[clap(short, long)]
+    port: Option<u16>,
 
     /// Reservationnumber for a game
     #[clap(short, long)]
     reservation: Option<String>,
 
-    /// Room ID for a game
+    /// Room ID of an open game to join
     #[clap(long)]
     room: Option<String>,
+
+    /// TOML file with further settings, which are overridden by the environment and the
+    /// command line
+    #[clap(short, long)]
+    config: Option<PathBuf>,
+
+    /// Directory to save a record of every played game into
+    #[clap(long)]
+    record_dir: Option<PathBuf>,
+
+    /// Number of games to play one after another [default: 1]
+    #[clap(short, long)]
+    games: Option<usize>,
+
+    /// Failed connections in a row, after which the client gives up [default: 5]
+    #[clap(long)]
+    max_attempts: Option<u32>,
+
+    /// Algorithm to play with [default: minmax]
+    #[clap(short, long)]
+    algorithm: Option<String>,
+
+    /// Search depth [default: 6]
+    #[clap(short, long)]
+    depth: Option<u8>,
+
+    /// Upper limit for the time of each search in milliseconds
+    #[clap(long)]
+    max_time: Option<u64>,
+
+    /// Evaluation function [default: eval-2603-1]
+    #[clap(short, long)]
+    evaluation: Option<String>,
+
+    /// Time for each move in milliseconds as given by the server [default: 2000]
+    #[clap(long)]
+    move_time: Option<u64>,
+
+    /// Time in milliseconds to keep back of each move against timeouts [default: 150]
+    #[clap(long)]
+    safety_margin: Option<u64>,
+
+    /// One of off, error, warn, info, debug and trace [default: info]
+    #[clap(long)]
+    log_level: Option<String>,
+
+    /// Format of the log, text or json [default: text]
+    #[clap(long)]
+    log_format: Option<LogFormat>,
+
+    /// Directory to save the telemetry of every played game into, one JSON record per move
+    #[clap(long)]
+    telemetry_dir: Option<PathBuf>,
+
+    /// List the available algorithms and evaluation functions
+    #[clap(long)]
+    list: bool,
+
+    /// Print the resulting configuration as TOML and exit
+    #[clap(long)]
+    print_config: bool,
+}
+
+impl Args {
+    fn layer(&self) -> ConfigLayer {
+        ConfigLayer {
+            connection: ConnectionLayer {
+                host: self.host.clone(),
+                port: self.port,
+                reservation: self.reservation.clone(),
+                room: self.room.clone(),
+                games: self.games,
+                max_attempts: self.max_attempts,
+            },
+            algorithm: AlgorithmSettings {
+                name: self.algorithm.clone(),
+                depth: self.depth,
+                max_time_ms: self.max_time,
+                evaluation: self.evaluation.clone(),
+            },
+            time: TimeLayer {
+                move_time_ms: self.move_time,
+                safety_margin_ms: self.safety_margin,
+                ..TimeLayer::default()
+            },
+            logging: LoggingLayer {
+                level: self.log_level.clone(),
+                format: self.log_format,
+            },
+            output: OutputLayer {
+                record_dir: self.record_dir.clone(),
+                telemetry_dir: self.telemetry_dir.clone(),
+            },
+        }
+    }
+}
+
+/// Stacks the defaults, the config file, the environment and the command line.
+fn load_config(args: &Args) -> Result<Config, ConfigError> {
+    let path = args
+        .config
+        .clone()
+        .or_else(|| ConfigLayer::config_path(env::vars()));
+    let file = match path {
+        Some(path) => ConfigLayer::load(&path)?,
+        None => ConfigLayer::default(),
+    };
+    let env = ConfigLayer::from_env(env::vars())?;
+    ConfigLayer::default()
+        .or(file)
+        .or(env)
+        .or(args.layer())
+        .resolve()
 }
 
 fn main() {
     let args = Args::parse();
+    if args.list {
+        print!("{}", registry::describe());
+        return;
+    }
+
+    let config = load_config(&args);
+    match &config {
+        Ok(config) => logging::init(config.log_level(), config.logging.format),
+        Err(_) => logging::init(LevelFilter::Info, LogFormat::default()),
+    }
+    let config = config.unwrap_or_else(|err| fail(err));
+    let algorithm = config
+        .algorithm
+        .clone()
+        .build()
+        .unwrap_or_else(|err| fail(err));
+    if args.print_config {
+        print!("{}", config.to_toml());
+        return;
+    }
+
+    log::info!("Playing with {}", algorithm.name());
+    let mut client = Client::with_options(algorithm, config.client_options());
+    let session = SessionConfig {
+        games: config.connection.games,
+        max_attempts: config.connection.max_attempts,
+        ..SessionConfig::default()
+    };
+    let connection = &config.connection;
+    if client
+        .play(&connection.host, connection.port, &session)
+        .is_err()
+    {
+        std::process::exit(1);
+    }
+}
 
-    Builder::new()
-        .parse_env(&env::var("MY_APP_LOG").unwrap_or_default())
-        .format(|buf, record| {
-            writeln!(
-                buf,
-                "{} [{}] - {}",
-                Local::now().format("%Y-%m-%dT%H:%M:%S"),
-                record.level(),
-                record.args()
-            )
-        })
-        .filter_level(LevelFilter::Info)
-        .target(Target::Stdout)
-        .init();
-
-    let algorithm = MinMax::new(6, EVAL_2603_1);
-    let mut client = Client::new(algorithm, args.reservation.clone());
-
-    log::info!("Reservation: {:?}", args.reservation.clone());
-
-    let _result = client
-        .connect(&args.host, args.port)
-        .expect("Failed to connect to Server");
+fn fail(err: impl Display) -> ! {
+    log::error!("{}", err);
+    std::process::exit(2);
 }
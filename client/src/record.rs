@@ -0,0 +1,446 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use socha_client_2022::protocol::ScoreCause;
+
+use crate::game::{Board, Fen, GameError, Gamestate, IGamestate, Move, Team};
+
+/// A single ply of a recorded game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordedMove {
+    pub mov: Move,
+    /// Time the player took for the move, if known
+    pub time: Option<Duration>,
+    /// Evaluation of the move by our search, if it was our move
+    pub score: Option<i32>,
+}
+
+impl RecordedMove {
+    pub fn new(mov: Move) -> Self {
+        Self {
+            mov,
+            time: None,
+            score: None,
+        }
+    }
+}
+
+/// Outcome of a recorded game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordResult {
+    Winner(Team),
+    Draw,
+    Unfinished,
+}
+
+/// A complete game, which can be written to and read from a PGN like text format:
+///
+/// ```text
+/// [Red "MinMax(depth 6)"]
+/// [Blue "?"]
+/// [Date "2022-04-01T12:00:00"]
+/// [Fen "v1 hmrs.../... r 0 0/0"]
+/// [Result "1-0"]
+/// [Cause "Regular"]
+///
+/// 1. Ra1-b3 {time=1803ms score=120} Mh8-g8 {time=950ms}
+/// 2. ...
+/// 1-0
+/// ```
+///
+/// Moves are written in the notation of [`Move::notation`], optionally followed by a
+/// comment with the time taken and the evaluation of the search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    /// Position the game started from
+    pub start: Gamestate,
+    /// Name or algorithm of the player of team ONE
+    pub red: String,
+    /// Name or algorithm of the player of team TWO
+    pub blue: String,
+    pub date: Option<String>,
+    pub moves: Vec<RecordedMove>,
+    pub result: RecordResult,
+    pub cause: Option<ScoreCause>,
+    /// Any further tags, which are kept as they are
+    pub tags: BTreeMap<String, String>,
+}
+
+impl GameRecord {
+    /// Starts a new, unfinished record from the given position.
+    pub fn new(start: Gamestate, red: String, blue: String) -> Self {
+        Self {
+            start,
+            red,
+            blue,
+            date: None,
+            moves: Vec::new(),
+            result: RecordResult::Unfinished,
+            cause: None,
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// Returns: every position of the game, from the start to after the last move
+    pub fn positions(&self) -> Vec<Gamestate> {
+        let mut state = self.start;
+        let mut positions = vec![state];
+        for recorded in self.moves.iter() {
+            state.apply_move(&recorded.mov);
+            positions.push(state);
+        }
+        positions
+    }
+
+    /// Returns: the position after the last recorded move
+    pub fn final_state(&self) -> Gamestate {
+        let mut state = self.start;
+        for recorded in self.moves.iter() {
+            state.apply_move(&recorded.mov);
+        }
+        state
+    }
+
+    /// Writes the record into a new file in the given directory, creating the
+    /// directory if necessary.
+    /// Returns: the path of the written file
+    pub fn write_to_dir<P: AsRef<Path>>(&self, dir: P) -> io::Result<PathBuf> {
+        fs::create_dir_all(&dir)?;
+        let stamp = self
+            .date
+            .clone()
+            .unwrap_or_else(|| format!("{:x}", self.start.hash))
+            .replace(':', "-");
+
+        let mut path = dir.as_ref().join(format!("game-{}.txt", stamp));
+        let mut index = 1;
+        while path.exists() {
+            index += 1;
+            path = dir.as_ref().join(format!("game-{}-{}.txt", stamp, index));
+        }
+        fs::write(&path, self.to_string())?;
+        Ok(path)
+    }
+
+    /// Reads a record from a file.
+    pub fn read_from<P: AsRef<Path>>(path: P) -> Result<Self, RecordError> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+fn result_to_str(result: RecordResult) -> &'static str {
+    match result {
+        RecordResult::Winner(Team::ONE) => "1-0",
+        RecordResult::Winner(Team::TWO) => "0-1",
+        RecordResult::Draw => "1/2-1/2",
+        RecordResult::Unfinished => "*",
+    }
+}
+
+fn result_from_str(s: &str) -> Option<RecordResult> {
+    match s {
+        "1-0" => Some(RecordResult::Winner(Team::ONE)),
+        "0-1" => Some(RecordResult::Winner(Team::TWO)),
+        "1/2-1/2" => Some(RecordResult::Draw),
+        "*" => Some(RecordResult::Unfinished),
+        _ => None,
+    }
+}
+
+fn cause_from_str(s: &str) -> Option<ScoreCause> {
+    match s {
+        "Regular" => Some(ScoreCause::Regular),
+        "Left" => Some(ScoreCause::Left),
+        "RuleViolation" => Some(ScoreCause::RuleViolation),
+        "SoftTimeout" => Some(ScoreCause::SoftTimeout),
+        "HardTimeout" => Some(ScoreCause::HardTimeout),
+        "Unknown" => Some(ScoreCause::Unknown),
+        _ => None,
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[Red \"{}\"]", self.red)?;
+        writeln!(f, "[Blue \"{}\"]", self.blue)?;
+        if let Some(date) = &self.date {
+            writeln!(f, "[Date \"{}\"]", date)?;
+        }
+        writeln!(f, "[Fen \"{}\"]", self.start.to_fen())?;
+        writeln!(f, "[Result \"{}\"]", result_to_str(self.result))?;
+        if let Some(cause) = self.cause {
+            writeln!(f, "[Cause \"{:?}\"]", cause)?;
+        }
+        for (key, value) in self.tags.iter() {
+            writeln!(f, "[{} \"{}\"]", key, value)?;
+        }
+        writeln!(f)?;
+
+        let mut state = self.start;
+        for (ply, recorded) in self.moves.iter().enumerate() {
+            if ply % 2 == 0 {
+                if ply > 0 {
+                    writeln!(f)?;
+                }
+                write!(f, "{}. ", ply / 2 + 1)?;
+            } else {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", recorded.mov.notation(&state))?;
+
+            let mut comment = vec![];
+            if let Some(time) = recorded.time {
+                comment.push(format!("time={}ms", time.as_millis()));
+            }
+            if let Some(score) = recorded.score {
+                comment.push(format!("score={}", score));
+            }
+            if !comment.is_empty() {
+                write!(f, " {{{}}}", comment.join(" "))?;
+            }
+            state.apply_move(&recorded.mov);
+        }
+        if !self.moves.is_empty() {
+            writeln!(f)?;
+        }
+        writeln!(f, "{}", result_to_str(self.result))
+    }
+}
+
+/// Errors while reading a [`GameRecord`].
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    /// A line of the record could not be understood.
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// A move could not be resolved against the position it was played in.
+    Move {
+        ply: usize,
+        error: GameError,
+    },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Io(err) => write!(f, "{}", err),
+            RecordError::Syntax { line, message } => write!(f, "Line {}: {}", line, message),
+            RecordError::Move { ply, error } => write!(f, "Ply {}: {}", ply, error),
+        }
+    }
+}
+
+impl Error for RecordError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RecordError::Io(err) => Some(err),
+            RecordError::Syntax { .. } => None,
+            RecordError::Move { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for RecordError {
+    fn from(err: io::Error) -> Self {
+        RecordError::Io(err)
+    }
+}
+
+/// Parses a tag line of the form `[Key "Value"]`.
+fn parse_tag(line: &str) -> Option<(&str, &str)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (key, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((key, value))
+}
+
+/// Adds the key=value pairs of a move comment to the recorded move.
+fn apply_comment(recorded: &mut RecordedMove, comment: &str) {
+    for (key, value) in comment.split_whitespace().filter_map(|p| p.split_once('=')) {
+        match key {
+            "time" => {
+                if let Ok(millis) = value.trim_end_matches("ms").parse() {
+                    recorded.time = Some(Duration::from_millis(millis));
+                }
+            }
+            "score" => recorded.score = value.parse().ok(),
+            _ => {}
+        }
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, RecordError> {
+        let mut record = GameRecord::new(Gamestate::new(Board::empty()), "?".into(), "?".into());
+        let mut start = None;
+        let mut result_tag = None;
+        let mut lines = s.lines().enumerate().peekable();
+
+        while let Some((index, line)) = lines.next_if(|(_, line)| !line.trim().is_empty()) {
+            let syntax = |message: String| RecordError::Syntax {
+                line: index + 1,
+                message,
+            };
+            let (key, value) =
+                parse_tag(line.trim()).ok_or_else(|| syntax(format!("Invalid tag {}", line)))?;
+            match key {
+                "Red" => record.red = value.to_string(),
+                "Blue" => record.blue = value.to_string(),
+                "Date" => record.date = Some(value.to_string()),
+                "Fen" => {
+                    start =
+                        Some(Gamestate::load_fen(value).map_err(|err| syntax(err.to_string()))?);
+                }
+                "Result" => {
+                    result_tag = Some(
+                        result_from_str(value)
+                            .ok_or_else(|| syntax(format!("Invalid result {}", value)))?,
+                    )
+                }
+                "Cause" => {
+                    record.cause = Some(
+                        cause_from_str(value)
+                            .ok_or_else(|| syntax(format!("Invalid cause {}", value)))?,
+                    )
+                }
+                _ => {
+                    record.tags.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+
+        record.start = start.ok_or(RecordError::Syntax {
+            line: 1,
+            message: "Missing Fen tag".to_string(),
+        })?;
+        record.result = result_tag.unwrap_or(RecordResult::Unfinished);
+
+        let mut state = record.start;
+        let mut terminated = false;
+        for (index, line) in lines {
+            let syntax = |message: String| RecordError::Syntax {
+                line: index + 1,
+                message,
+            };
+            let mut rest = line.trim();
+            while !rest.is_empty() {
+                if terminated {
+                    return Err(syntax(format!("Unexpected {} after the result", rest)));
+                }
+                if let Some(comment) = rest.strip_prefix('{') {
+                    let (comment, after) = comment
+                        .split_once('}')
+                        .ok_or_else(|| syntax("Unterminated comment".to_string()))?;
+                    let recorded = record
+                        .moves
+                        .last_mut()
+                        .ok_or_else(|| syntax("Comment before the first move".to_string()))?;
+                    apply_comment(recorded, comment);
+                    rest = after.trim_start();
+                    continue;
+                }
+
+                let (token, after) =
+                    rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+                rest = after.trim_start();
+
+                if token.ends_with('.')
+                    && token[..token.len() - 1].bytes().all(|b| b.is_ascii_digit())
+                {
+                    continue;
+                }
+                if let Some(result) = result_from_str(token) {
+                    if result_tag.is_none() {
+                        record.result = result;
+                    }
+                    terminated = true;
+                    continue;
+                }
+
+                let mov =
+                    Move::from_notation(token, &state).map_err(|error| RecordError::Move {
+                        ply: record.moves.len() + 1,
+                        error,
+                    })?;
+                state.apply_move(&mov);
+                record.moves.push(RecordedMove::new(mov));
+            }
+        }
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::SliceRandom;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro128Plus;
+
+    fn random_record(seed: u64) -> GameRecord {
+        let mut rng = Xoshiro128Plus::seed_from_u64(seed);
+        let start = Gamestate::new_random(&mut rng);
+        let mut record = GameRecord::new(start, "MinMax(depth 6)".into(), "?".into());
+        record.date = Some("2022-04-01T12:00:00".into());
+
+        let mut state = start;
+        while !state.game_over() {
+            let mov = *state
+                .available_moves_current_player()
+                .choose(&mut rng)
+                .unwrap();
+            let mut recorded = RecordedMove::new(mov);
+            if state.current_player() == Team::ONE {
+                recorded.time = Some(Duration::from_millis(1500 + state.turn as u64));
+                recorded.score = Some(-(state.turn as i32) * 100);
+            }
+            record.moves.push(recorded);
+            state.apply_move(&mov);
+        }
+        record.result = match state.winner() {
+            Some(team) => RecordResult::Winner(team),
+            None => RecordResult::Draw,
+        };
+        record.cause = Some(ScoreCause::Regular);
+        record
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        for seed in 0..10 {
+            let record = random_record(seed);
+            let parsed = record.to_string().parse::<GameRecord>().unwrap();
+            assert_eq!(parsed, record);
+            assert_eq!(parsed.final_state().to_fen(), record.final_state().to_fen());
+        }
+    }
+
+    #[test]
+    fn test_record_errors() {
+        let record = random_record(1).to_string();
+
+        let broken = record.replacen("[Fen", "[Fen \"x\"]\n[Fen", 1);
+        assert!(matches!(
+            broken.parse::<GameRecord>(),
+            Err(RecordError::Syntax { line: 4, .. })
+        ));
+
+        let first_move = record.lines().nth(7).unwrap().split(' ').nth(1).unwrap();
+        let illegal = record.replacen(first_move, "Ra1-a2", 1);
+        assert!(matches!(
+            illegal.parse::<GameRecord>(),
+            Err(RecordError::Move { ply: 1, .. })
+        ));
+    }
+}
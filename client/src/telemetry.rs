@@ -0,0 +1,147 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::SearchInfo;
+use crate::game::{Fen, Gamestate, IGamestate, Team};
+
+/// Statistics of one of our moves. A game's telemetry is a file with one record per
+/// line in JSON, so the records of many games can be concatenated and aggregated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MoveTelemetry {
+    pub turn: u8,
+    /// Position the move was searched in
+    pub fen: String,
+    pub legal_moves: usize,
+    /// Deepest search of the root moves, if the algorithm searches by depth
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    /// Nodes per second of the search
+    pub nps: Option<u64>,
+    /// Evaluation of the chosen move from our perspective
+    pub score: Option<i32>,
+    /// Time from the move request to sending the move
+    pub time_ms: u64,
+    /// Time the opponent took for the preceding move, if any
+    pub opponent_time_ms: Option<u64>,
+}
+
+impl MoveTelemetry {
+    pub fn new(
+        state: &Gamestate,
+        team: Team,
+        info: &SearchInfo,
+        time: Duration,
+        opponent_time: Option<Duration>,
+    ) -> Self {
+        let nps = match (info.nodes, info.search_time) {
+            (Some(nodes), Some(search_time)) if !search_time.is_zero() => {
+                Some((nodes as f64 / search_time.as_secs_f64()) as u64)
+            }
+            _ => None,
+        };
+        Self {
+            turn: state.turn,
+            fen: state.to_fen(),
+            legal_moves: state.available_moves(team).len(),
+            depth: info.depth,
+            nodes: info.nodes,
+            nps,
+            score: info.score,
+            time_ms: time.as_millis() as u64,
+            opponent_time_ms: opponent_time.map(|time| time.as_millis() as u64),
+        }
+    }
+}
+
+/// Writes the telemetry of a single game, see [`MoveTelemetry`].
+pub struct TelemetryWriter {
+    path: PathBuf,
+    out: BufWriter<File>,
+}
+
+impl TelemetryWriter {
+    /// Creates a new telemetry file in the given directory, named by the current time
+    /// and our team.
+    pub fn create<P: AsRef<Path>>(dir: P, team: Team) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let stamp = Local::now().format("%Y-%m-%dT%H-%M-%S");
+        let mut path = dir
+            .as_ref()
+            .join(format!("telemetry-{}-{}.jsonl", stamp, team));
+        let mut index = 1;
+        while path.exists() {
+            index += 1;
+            path = dir
+                .as_ref()
+                .join(format!("telemetry-{}-{}-{}.jsonl", stamp, team, index));
+        }
+        let out = BufWriter::new(File::create(&path)?);
+        Ok(Self { path, out })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends a record, flushing it right away so nothing is lost if the client dies.
+    pub fn write(&mut self, telemetry: &MoveTelemetry) -> io::Result<()> {
+        serde_json::to_writer(&mut self.out, telemetry)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()
+    }
+}
+
+/// Reads the records of a telemetry file.
+pub fn read_telemetry<P: AsRef<Path>>(path: P) -> io::Result<Vec<MoveTelemetry>> {
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(io::Error::from))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_write_and_read() {
+        let state = Gamestate::new_random(&mut StdRng::seed_from_u64(3));
+        let info = SearchInfo {
+            score: Some(12),
+            depth: Some(4),
+            nodes: Some(5000),
+            search_time: Some(Duration::from_millis(500)),
+        };
+        let telemetry = MoveTelemetry::new(
+            &state,
+            Team::ONE,
+            &info,
+            Duration::from_millis(520),
+            Some(Duration::from_millis(80)),
+        );
+        assert_eq!(telemetry.nps, Some(10000));
+        assert_eq!(
+            telemetry.legal_moves,
+            state.available_moves(Team::ONE).len()
+        );
+        assert_eq!(telemetry.opponent_time_ms, Some(80));
+
+        let dir = std::env::temp_dir().join(format!("telemetry-test-{}", std::process::id()));
+        let mut writer = TelemetryWriter::create(&dir, Team::ONE).unwrap();
+        let second = TelemetryWriter::create(&dir, Team::ONE).unwrap();
+        assert_ne!(writer.path(), second.path());
+        writer.write(&telemetry).unwrap();
+        writer.write(&telemetry).unwrap();
+        let read = read_telemetry(writer.path()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(read, vec![telemetry.clone(), telemetry]);
+    }
+}
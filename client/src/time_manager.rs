@@ -0,0 +1,295 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::game::{Gamestate, IGamestate};
+
+/// Source of monotonic time, so the time manager can be tested without waiting.
+pub trait Clock: Send {
+    /// Returns: the time elapsed since some fixed point in the past
+    fn now(&self) -> Duration;
+}
+
+/// The real clock.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A clock which only moves when told to. Clones share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct FakeClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl FakeClock {
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeConfig {
+    /// Time the server allows for each move
+    pub move_time: Duration,
+    /// Kept free in any case, for scheduling hiccups and sending the move
+    pub safety_margin: Duration,
+    /// Assumed round trip overhead until it has been measured
+    pub initial_overhead: Duration,
+    /// Fraction of the available time used for ordinary moves, critical moves use all of it
+    pub normal_fraction: f64,
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        Self {
+            move_time: Duration::from_millis(2000),
+            safety_margin: Duration::from_millis(150),
+            initial_overhead: Duration::from_millis(50),
+            normal_fraction: 0.75,
+        }
+    }
+}
+
+/// From this turn on, every move is considered critical.
+const LATE_GAME_TURN: u8 = 40;
+
+/// Decides how long to think about each move.
+///
+/// The server measures from sending the move request until our move arrives, so the
+/// network round trip counts against our time. It is learned from the time between
+/// sending a move and receiving the state update which contains it, estimated like
+/// TCP's retransmission timeout: smoothed average plus four times the mean deviation.
+#[derive(Debug, Clone)]
+pub struct TimeManager<C: Clock = SystemClock> {
+    clock: C,
+    config: TimeConfig,
+    /// Smoothed round trip time and its mean deviation, once measured
+    round_trip: Option<(Duration, Duration)>,
+    requested_at: Option<Duration>,
+    sent_at: Option<Duration>,
+}
+
+impl TimeManager<SystemClock> {
+    pub fn new(config: TimeConfig) -> Self {
+        Self::with_clock(config, SystemClock::default())
+    }
+}
+
+impl<C: Clock> TimeManager<C> {
+    pub fn with_clock(config: TimeConfig, clock: C) -> Self {
+        Self {
+            clock,
+            config,
+            round_trip: None,
+            requested_at: None,
+            sent_at: None,
+        }
+    }
+
+    pub fn config(&self) -> &TimeConfig {
+        &self.config
+    }
+
+    /// Returns: the estimated overhead of a round trip to the server
+    pub fn overhead(&self) -> Duration {
+        match self.round_trip {
+            Some((average, deviation)) => average + 4 * deviation,
+            None => self.config.initial_overhead,
+        }
+    }
+
+    /// Returns: the time after the move request, by which the move has to be sent
+    pub fn hard_limit(&self) -> Duration {
+        self.config
+            .move_time
+            .saturating_sub(self.config.safety_margin)
+            .saturating_sub(self.overhead())
+    }
+
+    /// Returns: the time left until the hard limit of the current move
+    pub fn remaining(&self) -> Duration {
+        let elapsed = self.elapsed().unwrap_or_default();
+        self.hard_limit().saturating_sub(elapsed)
+    }
+
+    /// Returns: the time since the current move was requested
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.requested_at
+            .map(|requested| self.clock.now().saturating_sub(requested))
+    }
+
+    /// Returns: the time the search should take for the given state.
+    /// Forced moves get no time at all, critical and late moves all of the available time.
+    pub fn budget(&self, state: &Gamestate) -> Duration {
+        let available = self.remaining();
+        if Self::is_forced(state) {
+            Duration::ZERO
+        } else if Self::is_critical(state) {
+            available
+        } else {
+            available.mul_f64(self.config.normal_fraction)
+        }
+    }
+
+    /// A move is forced, if there is no choice.
+    pub fn is_forced(state: &Gamestate) -> bool {
+        state.count_moves(state.current_player()) <= 1
+    }
+
+    /// A move is critical late in the game, or once a team is about to win by ambers.
+    pub fn is_critical(state: &Gamestate) -> bool {
+        state.turn >= LATE_GAME_TURN || state.ambers.iter().any(|&ambers| ambers >= 1)
+    }
+
+    /// Starts the clock of a new move, call as soon as the move request arrives.
+    pub fn move_requested(&mut self) {
+        self.requested_at = Some(self.clock.now());
+        self.sent_at = None;
+    }
+
+    /// Stops the clock of the current move.
+    /// Returns: the time it took
+    pub fn move_sent(&mut self) -> Duration {
+        let took = self.elapsed().unwrap_or_default();
+        self.requested_at = None;
+        self.sent_at = Some(self.clock.now());
+        took
+    }
+
+    /// Call when the state update containing our last move arrives, which completes a
+    /// round trip.
+    /// Returns: the measured round trip, if a move was sent
+    pub fn own_move_confirmed(&mut self) -> Option<Duration> {
+        let sample = self.clock.now().saturating_sub(self.sent_at.take()?);
+        self.round_trip = Some(match self.round_trip {
+            None => (sample, sample / 2),
+            Some((average, deviation)) => {
+                let difference = sample.abs_diff(average);
+                ((average * 7 + sample) / 8, (deviation * 3 + difference) / 4)
+            }
+        });
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Board, Piece, PieceType, Team};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn manager() -> (TimeManager<FakeClock>, FakeClock) {
+        let clock = FakeClock::default();
+        (
+            TimeManager::with_clock(TimeConfig::default(), clock.clone()),
+            clock,
+        )
+    }
+
+    fn sample(manager: &mut TimeManager<FakeClock>, clock: &FakeClock, round_trip: u64) {
+        manager.move_requested();
+        manager.move_sent();
+        clock.advance(Duration::from_millis(round_trip));
+        manager.own_move_confirmed();
+    }
+
+    #[test]
+    fn test_budget_by_phase() {
+        let (mut manager, clock) = manager();
+        manager.move_requested();
+        let limit = Duration::from_millis(2000 - 150 - 50);
+        assert_eq!(manager.hard_limit(), limit);
+
+        let mut state = Gamestate::new_random(&mut StdRng::seed_from_u64(0));
+        assert_eq!(manager.budget(&state), limit.mul_f64(0.75));
+        state.turn = LATE_GAME_TURN;
+        assert_eq!(manager.budget(&state), limit);
+        state.turn = 2;
+        state.ambers[Team::TWO as usize] = 1;
+        assert_eq!(manager.budget(&state), limit);
+
+        clock.advance(Duration::from_millis(300));
+        assert_eq!(manager.remaining(), limit - Duration::from_millis(300));
+        assert_eq!(manager.budget(&state), limit - Duration::from_millis(300));
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(manager.budget(&state), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_forced_move() {
+        let (mut manager, _) = manager();
+        manager.move_requested();
+        // A cockle in the corner can only move to b2
+        let mut board = Board::empty();
+        board.set_piece(0, Piece::new(PieceType::Herzmuschel, Team::ONE, false));
+        board.set_piece(63, Piece::new(PieceType::Herzmuschel, Team::TWO, false));
+        let state = Gamestate::new(board);
+        assert_eq!(state.available_moves_current_player().len(), 1);
+        assert!(TimeManager::<FakeClock>::is_forced(&state));
+        assert_eq!(manager.budget(&state), Duration::ZERO);
+
+        let state = Gamestate::new_random(&mut StdRng::seed_from_u64(0));
+        assert!(!TimeManager::<FakeClock>::is_forced(&state));
+    }
+
+    #[test]
+    fn test_learns_overhead() {
+        let (mut manager, clock) = manager();
+        assert_eq!(manager.overhead(), Duration::from_millis(50));
+        assert_eq!(manager.own_move_confirmed(), None);
+
+        sample(&mut manager, &clock, 100);
+        // First sample: average 100, deviation 50
+        assert_eq!(manager.overhead(), Duration::from_millis(300));
+        for _ in 0..100 {
+            sample(&mut manager, &clock, 100);
+        }
+        let overhead = manager.overhead();
+        assert!(overhead >= Duration::from_millis(100));
+        assert!(overhead < Duration::from_millis(101));
+
+        // A slower connection increases the overhead
+        sample(&mut manager, &clock, 300);
+        assert!(manager.overhead() > Duration::from_millis(200));
+        assert!(manager.hard_limit() < Duration::from_millis(1650));
+    }
+
+    #[test]
+    fn test_move_timing() {
+        let (mut manager, clock) = manager();
+        assert_eq!(manager.elapsed(), None);
+        manager.move_requested();
+        clock.advance(Duration::from_millis(700));
+        assert_eq!(manager.elapsed(), Some(Duration::from_millis(700)));
+        assert_eq!(manager.move_sent(), Duration::from_millis(700));
+        assert_eq!(manager.elapsed(), None);
+        clock.advance(Duration::from_millis(40));
+        assert_eq!(
+            manager.own_move_confirmed(),
+            Some(Duration::from_millis(40))
+        );
+        // Only the first update after a move completes a round trip
+        assert_eq!(manager.own_move_confirmed(), None);
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use log::error;
+
+use crate::algorithms::heuristics::EVAL_2603_1;
+use crate::algorithms::{Algorithm, BestSoFar};
+use crate::game::{Fen, Gamestate, IGamestate, Move, Team};
+
+/// How a search under the watchdog ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOutcome {
+    Finished(Move),
+    /// The deadline passed, with the best move the search had found until then
+    TimedOut(Option<Move>),
+    /// The search panicked, with the best move it had found until then
+    Panicked(Option<Move>),
+}
+
+impl fmt::Display for SearchOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchOutcome::Finished(mov) => write!(f, "finished with {}", mov),
+            SearchOutcome::TimedOut(_) => write!(f, "timed out"),
+            SearchOutcome::Panicked(_) => write!(f, "panicked"),
+        }
+    }
+}
+
+/// Runs the search on a worker thread and waits at most until the deadline.
+///
+/// The worker searches with a clone of the algorithm, which replaces the original once
+/// the search finished. A worker that misses the deadline is cancelled through
+/// [`BestSoFar::cancel`], it is left to stop in the background and its result is dropped.
+pub fn search_with_deadline<A: Algorithm + Send + 'static>(
+    algorithm: &mut A,
+    state: Gamestate,
+    my_team: Team,
+    deadline: Duration,
+) -> SearchOutcome {
+    let best_so_far = BestSoFar::default();
+    let mut worker = algorithm.clone();
+    worker.report_progress_to(best_so_far.clone());
+
+    let (sender, receiver) = mpsc::channel();
+    let spawned = thread::Builder::new()
+        .name("search".to_string())
+        .spawn(move || {
+            let mov = worker.best_move(state, my_team);
+            // The receiver is gone after a timeout
+            let _ = sender.send((mov, worker));
+        });
+    if spawned.is_err() {
+        return SearchOutcome::Panicked(None);
+    }
+
+    match receiver.recv_timeout(deadline) {
+        Ok((mov, worker)) => {
+            *algorithm = worker;
+            SearchOutcome::Finished(mov)
+        }
+        Err(RecvTimeoutError::Timeout) => {
+            best_so_far.cancel();
+            SearchOutcome::TimedOut(best_so_far.get())
+        }
+        Err(RecvTimeoutError::Disconnected) => SearchOutcome::Panicked(best_so_far.get()),
+    }
+}
+
+/// Cheap fallback, when there is no move from the search: the move leading to the
+/// best evaluation after a single ply.
+/// Returns: None, if there is no legal move
+pub fn greedy_move(state: &Gamestate) -> Option<Move> {
+    let team = state.current_player();
+    state
+        .available_moves_current_player()
+        .iter()
+        .copied()
+        .max_by_key(|mov| {
+            let mut child = *state;
+            child.apply_move(mov);
+            EVAL_2603_1(&child, team)
+        })
+}
+
+/// Makes sure a move about to be played is legal, replacing a missing or illegal move
+/// by the [greedy fallback](greedy_move).
+/// Returns: None, if there is no legal move
+pub fn ensure_legal(state: &Gamestate, best: Option<Move>) -> Option<Move> {
+    match best.map(|best| (best, state.validate_move(&best))) {
+        Some((best, Ok(()))) => return Some(best),
+        Some((best, Err(err))) => error!(
+            "!!! Search produced an illegal move {} in {}: {} !!!",
+            best,
+            state.to_fen(),
+            err
+        ),
+        None => {}
+    }
+    let fallback = greedy_move(state)?;
+    error!("Falling back to {}", fallback.notation(state));
+    Some(fallback)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::RandomPick;
+    use crate::game::Board;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    /// Reports a move and then takes far too long, unless cancelled, or panics.
+    #[derive(Clone)]
+    struct Misbehaving {
+        panic: bool,
+        best_so_far: Option<BestSoFar>,
+        /// Set once the search noticed that it was cancelled
+        stopped: Arc<AtomicBool>,
+    }
+
+    impl Algorithm for Misbehaving {
+        fn best_move(&mut self, state: Gamestate, _my_team: Team) -> Move {
+            let mov = state.available_moves_current_player()[0];
+            if let Some(best_so_far) = &self.best_so_far {
+                best_so_far.set(mov);
+            }
+            if self.panic {
+                panic!("Search failed");
+            }
+            let started = Instant::now();
+            while started.elapsed() < Duration::from_secs(2) {
+                if matches!(&self.best_so_far, Some(best) if best.is_cancelled()) {
+                    self.stopped.store(true, Ordering::SeqCst);
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            mov
+        }
+
+        fn report_progress_to(&mut self, best_so_far: BestSoFar) {
+            self.best_so_far = Some(best_so_far);
+        }
+    }
+
+    fn state() -> Gamestate {
+        Gamestate::new_random(&mut StdRng::seed_from_u64(3))
+    }
+
+    #[test]
+    fn test_finished() {
+        let state = state();
+        let outcome =
+            search_with_deadline(&mut RandomPick, state, Team::ONE, Duration::from_secs(10));
+        match outcome {
+            SearchOutcome::Finished(mov) => assert!(state.is_legal(&mov)),
+            outcome => panic!("Unexpected outcome {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let state = state();
+        let mut algorithm = Misbehaving {
+            panic: false,
+            best_so_far: None,
+            stopped: Arc::default(),
+        };
+        let started = Instant::now();
+        let outcome =
+            search_with_deadline(&mut algorithm, state, Team::ONE, Duration::from_millis(100));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let expected = state.available_moves_current_player()[0];
+        assert_eq!(outcome, SearchOutcome::TimedOut(Some(expected)));
+
+        // The worker was told to stop
+        while !algorithm.stopped.load(Ordering::SeqCst) {
+            assert!(started.elapsed() < Duration::from_secs(1));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        let state = state();
+        let mut algorithm = Misbehaving {
+            panic: true,
+            best_so_far: None,
+            stopped: Arc::default(),
+        };
+        let outcome =
+            search_with_deadline(&mut algorithm, state, Team::ONE, Duration::from_secs(10));
+        let expected = state.available_moves_current_player()[0];
+        assert_eq!(outcome, SearchOutcome::Panicked(Some(expected)));
+    }
+
+    #[test]
+    fn test_greedy_move() {
+        let mut state = state();
+        while !state.game_over() {
+            let mov = greedy_move(&state).unwrap();
+            assert!(state.is_legal(&mov));
+            state.apply_move(&mov);
+        }
+    }
+
+    #[test]
+    fn test_ensure_legal() {
+        let state = state();
+        let legal = state.available_moves_current_player()[0];
+        assert_eq!(ensure_legal(&state, Some(legal)), Some(legal));
+        let illegal = Move {
+            from: legal.from,
+            to: legal.from,
+            piece: legal.piece,
+        };
+        assert_eq!(ensure_legal(&state, Some(illegal)), greedy_move(&state));
+        assert_eq!(ensure_legal(&state, None), greedy_move(&state));
+
+        let stuck = Gamestate::new(Board::empty());
+        assert_eq!(ensure_legal(&stuck, Some(legal)), None);
+    }
+}
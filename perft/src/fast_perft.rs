@@ -0,0 +1,225 @@
+//! A faster perft with the same counts as [`crate::serial_perft`]: the root moves are
+//! searched on several threads and subtrees reached more than once can be looked up
+//! in a hash table shared by all threads.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use client::game::{Gamestate, IGamestate};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerftOptions {
+    pub threads: usize,
+    /// Size of the hash table in MB, none to search without
+    pub hash_mb: Option<usize>,
+}
+
+/// Leaf counts of searched subtrees by Zobrist hash, turn and depth.
+///
+/// Entries are written without locks: each slot holds the key xor the count next to the
+/// count, so a slot torn by two threads writing at once doesn't match any key.
+pub struct PerftTable {
+    slots: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl PerftTable {
+    /// Creates a table with as many entries as fit into the given size, rounded down
+    /// to a power of two.
+    pub fn with_size_mb(size_mb: usize) -> Self {
+        let fitting = size_mb.max(1) * 1024 * 1024 / std::mem::size_of::<(AtomicU64, AtomicU64)>();
+        let len = 1 << (usize::BITS - 1 - fitting.leading_zeros());
+        Self {
+            slots: (0..len)
+                .map(|_| (AtomicU64::new(0), AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    /// The Zobrist hash doesn't include the turn, which decides the player to move and
+    /// the end of the game, so it is mixed in together with the depth.
+    fn key(state: &Gamestate, depth: u32) -> u64 {
+        let extra = ((state.turn as u64) << 32) | depth as u64;
+        state.hash ^ extra.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn slot(&self, key: u64) -> &(AtomicU64, AtomicU64) {
+        &self.slots[(key as usize) & (self.slots.len() - 1)]
+    }
+
+    fn probe(&self, key: u64) -> Option<u64> {
+        let (checked, count) = self.slot(key);
+        let count = count.load(Ordering::Relaxed);
+        // Empty slots hold 0 and 0, which only match a key of 0
+        (checked.load(Ordering::Relaxed) ^ count == key && count != 0).then_some(count)
+    }
+
+    fn store(&self, key: u64, count: u64) {
+        let (checked, stored) = self.slot(key);
+        checked.store(key ^ count, Ordering::Relaxed);
+        stored.store(count, Ordering::Relaxed);
+    }
+}
+
+fn perft_recursive(state: Gamestate, depth: u32, table: Option<&PerftTable>) -> u64 {
+    if depth == 0 || state.game_over() {
+        return 1;
+    }
+    let team = state.current_player();
+    if depth == 1 {
+        return (state.count_moves(team) as u64).max(1);
+    }
+
+    let key = PerftTable::key(&state, depth);
+    if let Some(count) = table.and_then(|table| table.probe(key)) {
+        return count;
+    }
+    let mut leaf_count = 0;
+    state.for_each_move(team, &mut |m| {
+        let mut child = state;
+        child.apply_move(&m);
+        leaf_count += perft_recursive(child, depth - 1, table);
+    });
+    let leaf_count = leaf_count.max(1);
+    if let Some(table) = table {
+        table.store(key, leaf_count);
+    }
+    leaf_count
+}
+
+/// Like [`crate::serial_perft::perft_divide`], but the root moves are handed out to the
+/// threads one by one, so threads finishing small subtrees take over the remaining moves.
+pub fn perft_divide_parallel(
+    starting_position: Gamestate,
+    depth: u32,
+    options: &PerftOptions,
+) -> Vec<(String, u64)> {
+    if depth == 0 || starting_position.game_over() {
+        return vec![];
+    }
+    let team = starting_position.current_player();
+    let moves = starting_position.available_moves(team);
+    let table = options.hash_mb.map(PerftTable::with_size_mb);
+    let next = AtomicUsize::new(0);
+    let divided = Mutex::new(Vec::with_capacity(moves.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..options.threads.clamp(1, moves.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let m = match moves.get(index) {
+                    Some(m) => *m,
+                    None => break,
+                };
+                let mut child = starting_position;
+                child.apply_move(&m);
+                let count = perft_recursive(child, depth - 1, table.as_ref());
+                let notation = m.notation(&starting_position);
+                divided.lock().unwrap().push((notation, count));
+            });
+        }
+    });
+
+    let mut divided = divided.into_inner().unwrap();
+    divided.sort();
+    divided
+}
+
+pub fn perft_parallel(starting_position: Gamestate, depth: u32, options: &PerftOptions) -> u64 {
+    let divided = perft_divide_parallel(starting_position, depth, options);
+    match divided.len() {
+        // The starting position is a leaf itself
+        0 => 1,
+        _ => divided.iter().map(|(_, count)| count).sum(),
+    }
+}
+
+/// Searches with and without the hash table.
+/// Returns: the root moves with their counts with and without the table, which differ
+pub fn check_consistency(
+    starting_position: Gamestate,
+    depth: u32,
+    options: &PerftOptions,
+) -> Vec<(String, u64, u64)> {
+    let hashed = PerftOptions {
+        hash_mb: Some(options.hash_mb.unwrap_or(64)),
+        ..*options
+    };
+    let unhashed = PerftOptions {
+        hash_mb: None,
+        ..*options
+    };
+    let with_table = perft_divide_parallel(starting_position, depth, &hashed);
+    let without_table = perft_divide_parallel(starting_position, depth, &unhashed);
+    with_table
+        .into_iter()
+        .zip(without_table)
+        .filter(|((_, hashed), (_, unhashed))| hashed != unhashed)
+        .map(|((notation, hashed), (_, unhashed))| (notation, hashed, unhashed))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_perft::{perft_divide, perft_up_to};
+    use client::game::Fen;
+
+    const FENS: [&str; 3] = [
+        "v1 hrrssmhm/8/8/8/8/8/8/MHMSSRRH r 0 0/0",
+        "v1 3m2r1/sm1hh2s/8/8/3S4/3HR3/2r*2M2/1R3M1S r 30 0/0",
+        "v1 4rm2/2h5/7m/2r*4s/4s3/2R5/5H1H/MM1SR*3 b 57 0/0",
+    ];
+
+    #[test]
+    fn test_same_as_serial_perft() {
+        let options = PerftOptions {
+            threads: 4,
+            hash_mb: Some(1),
+        };
+        for fen in FENS {
+            let state = Gamestate::load_fen(fen).unwrap();
+            for depth in 0..=4 {
+                assert_eq!(
+                    perft_parallel(state, depth, &options),
+                    perft_up_to(state, depth),
+                    "perft {} of {}",
+                    depth,
+                    fen
+                );
+            }
+            assert_eq!(
+                perft_divide_parallel(state, 3, &options),
+                perft_divide(state, 3)
+            );
+        }
+    }
+
+    #[test]
+    fn test_consistency() {
+        // A tiny table, so most entries are replaced
+        let options = PerftOptions {
+            threads: 3,
+            hash_mb: Some(1),
+        };
+        for fen in FENS {
+            let state = Gamestate::load_fen(fen).unwrap();
+            assert!(check_consistency(state, 4, &options).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_table() {
+        let table = PerftTable::with_size_mb(1);
+        let state = Gamestate::load_fen(FENS[0]).unwrap();
+        let key = PerftTable::key(&state, 3);
+        assert_eq!(table.probe(key), None);
+        table.store(key, 5958);
+        assert_eq!(table.probe(key), Some(5958));
+        // Other depths and turns of the same position are different entries
+        assert_ne!(PerftTable::key(&state, 2), key);
+        let mut later = state;
+        later.turn += 2;
+        assert_ne!(PerftTable::key(&later, 3), key);
+    }
+}
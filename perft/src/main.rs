@@ -1,31 +1,135 @@
 use std::time::SystemTime;
 use client::game::{Fen, Gamestate};
 use clap::Parser;
-use crate::slow_perft::perft_up_to;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use crate::fast_perft::{check_consistency, perft_divide_parallel, perft_parallel, PerftOptions};
+use crate::reference_perft::{reference_perft, run_differential};
+use crate::serial_perft::{perft_divide, perft_up_to};
+use client::game::ReferenceState;
 
-mod slow_perft;
+mod fast_perft;
+mod reference_perft;
+mod serial_perft;
 
 #[derive(Parser, Debug)]
 struct Args {
     /// Fen String of the gamestate
-    #[clap(short, long)]
-    fen: String,
+    #[clap(
+        short,
+        long,
+        required_unless_present_any = &["seed", "differential"],
+        conflicts_with = "seed"
+    )]
+    fen: Option<String>,
 
-    /// Port of the game server
+    /// Start from the random position generated from this seed instead of a FEN, or
+    /// generate the random games of the differential test from it
     #[clap(short, long)]
+    seed: Option<u64>,
+
+    /// Depth of the perft
+    #[clap(short, long, required_unless_present = "differential", default_value_t = 0)]
     depth: u32,
+
+    /// List the node count of every root move, to find the moves a discrepancy with
+    /// another implementation is hidden under
+    #[clap(long)]
+    divide: bool,
+
+    /// Number of threads, the root moves are split between
+    #[clap(short, long, default_value_t = 1)]
+    threads: usize,
+
+    /// Size of a hash table for subtrees reached more than once in MB
+    #[clap(long)]
+    hash: Option<usize>,
+
+    /// Compare the counts with and without the hash table
+    #[clap(long)]
+    check: bool,
+
+    /// Count with the naive reference rules instead of the bitboards
+    #[clap(long, conflicts_with_all = &["divide", "threads", "hash", "check", "differential"])]
+    reference: bool,
+
+    /// Play this many random games, comparing each move with the reference rules.
+    /// The games start from the FEN, or from random positions generated from the seed.
+    #[clap(long)]
+    differential: Option<u64>,
 }
 
 fn main() {
     let args = Args::parse();
 
-    let state = Gamestate::load_fen(&*args.fen.clone()).expect("Please input a valid FEN");
+    if let Some(games) = args.differential {
+        let mut rng = StdRng::seed_from_u64(args.seed.unwrap_or(0));
+        let fen = args.fen.clone();
+        let result = run_differential(games, &mut rng, |rng| match &fen {
+            Some(fen) => Gamestate::load_fen(fen).expect("Please input a valid FEN"),
+            None => Gamestate::new_random(rng),
+        });
+        match result {
+            Ok(plies) => println!("{} games with {} plies agree with the reference rules", games, plies),
+            Err(divergence) => {
+                println!("{}", divergence);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let state = match (&args.fen, args.seed) {
+        (Some(fen), _) => Gamestate::load_fen(fen).expect("Please input a valid FEN"),
+        (None, Some(seed)) => Gamestate::new_random(&mut StdRng::seed_from_u64(seed)),
+        (None, None) => unreachable!("clap requires a FEN or a seed"),
+    };
     let depth = args.depth;
 
+    println!("Perft of {}", state.to_fen());
     println!("Hash of state is {:x}", state.hash);
 
+    let options = PerftOptions {
+        threads: args.threads,
+        hash_mb: args.hash,
+    };
+    if args.check {
+        let mismatches = check_consistency(state, depth, &options);
+        for (notation, hashed, unhashed) in &mismatches {
+            println!("{}: {} with the hash table, {} without", notation, hashed, unhashed);
+        }
+        if !mismatches.is_empty() {
+            println!("{} root moves differ", mismatches.len());
+            std::process::exit(1);
+        }
+        println!("Counts with and without the hash table are the same");
+        return;
+    }
+    let fast = args.threads > 1 || args.hash.is_some();
+
     let start = SystemTime::now();
-    let move_count = perft_up_to(state, depth);
+    let move_count = if args.divide {
+        let divided = if fast {
+            perft_divide_parallel(state, depth, &options)
+        } else {
+            perft_divide(state, depth)
+        };
+        for (notation, count) in &divided {
+            println!("{}: {}", notation, count);
+        }
+        println!("{} root moves", divided.len());
+        match divided.len() {
+            // The starting position is a leaf itself
+            0 => 1,
+            _ => divided.iter().map(|(_, count)| count).sum(),
+        }
+    } else if args.reference {
+        reference_perft(&ReferenceState::from_gamestate(&state), depth)
+    } else if fast {
+        perft_parallel(state, depth, &options)
+    } else {
+        perft_up_to(state, depth)
+    };
     let dur = SystemTime::now().duration_since(start).unwrap();
     println!("Perft to depth {} took {:?}",depth, dur);
     println!("Perft {}: {} total moves", depth, move_count);
@@ -0,0 +1,77 @@
+//! Perft and random games with the naive rules of [`ReferenceState`], the baseline the
+//! bitboard implementation is checked against.
+
+use client::game::{compare_random_game, Divergence, Gamestate, ReferenceState};
+use rand::Rng;
+
+/// Counts like [`crate::serial_perft::perft_up_to`] with the reference rules.
+pub fn reference_perft(state: &ReferenceState, depth: u32) -> u64 {
+    if depth == 0 || state.game_over() {
+        return 1;
+    }
+    let moves = state.legal_moves();
+    if moves.is_empty() {
+        return 1;
+    }
+    moves
+        .iter()
+        .map(|m| {
+            let mut child = state.clone();
+            child.apply_move(m);
+            reference_perft(&child, depth - 1)
+        })
+        .sum()
+}
+
+/// Plays the given number of random games, each from the position returned by `start`,
+/// and compares every move with the reference rules.
+/// Returns: the number of compared plies, or the first divergence
+pub fn run_differential<R: Rng, F: FnMut(&mut R) -> Gamestate>(
+    games: u64,
+    rng: &mut R,
+    mut start: F,
+) -> Result<u64, Divergence> {
+    let mut plies = 0;
+    for game in 1..=games {
+        let state = start(rng);
+        plies += compare_random_game(state, rng)? as u64;
+        if game % 100_000 == 0 {
+            println!("{} games, {} plies without a difference", game, plies);
+        }
+    }
+    Ok(plies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial_perft::perft_up_to;
+    use client::game::Fen;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_same_as_serial_perft() {
+        for fen in [
+            "v1 hrrssmhm/8/8/8/8/8/8/MHMSSRRH r 0 0/0",
+            "v1 1m3shr/2h1r3/m7/5s2/5R2/8/5H1M/1HSSR1M1 r 14 1/1",
+            "v1 4rm2/2h5/7m/2r*4s/4s3/2R5/5H1H/MM1SR*3 b 57 0/0",
+        ] {
+            let state = Gamestate::load_fen(fen).unwrap();
+            let reference = ReferenceState::from_gamestate(&state);
+            for depth in 0..=3 {
+                assert_eq!(
+                    reference_perft(&reference, depth),
+                    perft_up_to(state, depth)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_differential() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let plies = run_differential(200, &mut rng, Gamestate::new_random).unwrap();
+        assert!(plies > 200);
+    }
+}
@@ -0,0 +1,124 @@
+//! Perft counts the leaf nodes of the game tree to a fixed depth, to validate the move
+//! generation against known counts or another implementation.
+//!
+//! The counts follow the usual definition:
+//! - a position searched at depth 0 is a single leaf, so `perft(state, 0) == 1` and
+//!   `perft(state, 1)` is the number of legal moves;
+//! - a position in which the game is over, or in which the player to move has no legal
+//!   move, is a leaf as well, wherever it is reached. Its subtree isn't searched, but it
+//!   is counted once.
+
+use client::game::{Gamestate, IGamestate};
+
+/// Returns: the number of leaf nodes of the game tree of `starting_position` at `depth`
+pub fn perft_up_to(starting_position: Gamestate, depth: u32) -> u64 {
+    perft_recursive(starting_position, depth)
+}
+
+fn perft_recursive(state: Gamestate, depth: u32) -> u64 {
+    if depth == 0 || state.game_over() {
+        return 1;
+    }
+    let team = state.current_player();
+    if depth == 1 {
+        return (state.count_moves(team) as u64).max(1);
+    }
+
+    let mut leaf_count = 0;
+    state.for_each_move(team, &mut |m| {
+        let mut child = state;
+        child.apply_move(&m);
+        leaf_count += perft_recursive(child, depth - 1);
+    });
+    leaf_count.max(1)
+}
+
+/// Splits [`perft_up_to`] by the moves of the starting position.
+/// Returns: the notation of each root move with its share of the total, sorted by notation.
+/// Empty, if the starting position is a leaf.
+pub fn perft_divide(starting_position: Gamestate, depth: u32) -> Vec<(String, u64)> {
+    let mut divided = vec![];
+    if depth == 0 || starting_position.game_over() {
+        return divided;
+    }
+    let team = starting_position.current_player();
+    starting_position.for_each_move(team, &mut |m| {
+        let mut child = starting_position;
+        child.apply_move(&m);
+        divided.push((m.notation(&starting_position), perft_recursive(child, depth - 1)));
+    });
+    divided.sort();
+    divided
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::game::Fen;
+
+    /// Counts for depth 0 and up, checked against a search over `available_moves`.
+    /// A change of any of them means the move generation or the rules changed.
+    const KNOWN_COUNTS: [(&str, &[u64]); 6] = [
+        (
+            "v1 hrrssmhm/8/8/8/8/8/8/MHMSSRRH r 0 0/0",
+            &[1, 18, 324, 5958, 109561, 2101060],
+        ),
+        (
+            "v1 sshmrmhr/8/8/8/8/8/8/RHMRMHSS r 0 0/0",
+            &[1, 17, 289, 5168, 92416],
+        ),
+        (
+            "v1 1m3shr/2h1r3/m7/5s2/5R2/8/5H1M/1HSSR1M1 r 14 0/0",
+            &[1, 28, 644, 16491, 384883],
+        ),
+        // Both teams one amber from winning
+        (
+            "v1 1m3shr/2h1r3/m7/5s2/5R2/8/5H1M/1HSSR1M1 r 14 1/1",
+            &[1, 28, 644, 16491, 384883],
+        ),
+        (
+            "v1 3m2r1/sm1hh2s/8/8/3S4/3HR3/2r*2M2/1R3M1S r 30 0/0",
+            &[1, 23, 500, 11468, 247902],
+        ),
+        // The game ends after turn 60, the last positions are leaves
+        (
+            "v1 4rm2/2h5/7m/2r*4s/4s3/2R5/5H1H/MM1SR*3 b 57 0/0",
+            &[1, 24, 456, 11005, 11005, 11005],
+        ),
+    ];
+
+    #[test]
+    fn test_known_counts() {
+        for (fen, counts) in KNOWN_COUNTS {
+            let state = Gamestate::load_fen(fen).unwrap();
+            for (depth, count) in counts.iter().enumerate() {
+                assert_eq!(
+                    perft_up_to(state, depth as u32),
+                    *count,
+                    "perft {} of {}",
+                    depth,
+                    fen
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_divide() {
+        for (fen, counts) in KNOWN_COUNTS {
+            let state = Gamestate::load_fen(fen).unwrap();
+            let divided = perft_divide(state, 3);
+            assert_eq!(divided.len() as u64, counts[1]);
+            assert_eq!(divided.iter().map(|(_, count)| count).sum::<u64>(), counts[3]);
+        }
+    }
+
+    #[test]
+    fn test_terminal_leaf() {
+        let mut state = Gamestate::load_fen(KNOWN_COUNTS[0].0).unwrap();
+        state.turn = 60;
+        assert!(state.game_over());
+        assert_eq!(perft_up_to(state, 3), 1);
+        assert!(perft_divide(state, 3).is_empty());
+    }
+}
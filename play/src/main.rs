@@ -0,0 +1,237 @@
+use std::io::{self, BufRead, Write};
+
+use clap::{ArgEnum, Parser};
+use client::algorithms::heuristics::EVAL_2603_1;
+use client::algorithms::{Algorithm, MinMax, RandomPick};
+use client::game::{Bitboard, Fen, Gamestate, IGamestate, Move, Team};
+use client::watchdog;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Play Ostseeschach against the bot in the terminal
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Fen String of the starting position, a random one is used otherwise
+    #[clap(short, long)]
+    fen: Option<String>,
+
+    /// Seed for the random starting position
+    #[clap(short, long)]
+    seed: Option<u64>,
+
+    /// Play as blue, the engine begins
+    #[clap(short, long)]
+    blue: bool,
+
+    /// Algorithm of the engine
+    #[clap(short, long, arg_enum, default_value = "minmax")]
+    algorithm: AlgorithmKind,
+
+    /// Search depth of the engine
+    #[clap(short, long, default_value_t = 4)]
+    depth: u8,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum AlgorithmKind {
+    Minmax,
+    Random,
+}
+
+const HELP: &str = "Enter a move like Ra1-b3 or a1b3, or one of the commands:
+  moves    list the legal moves
+  undo     take back your last move
+  switch   switch sides with the engine
+  fen      print the FEN of the position
+  help     show this help
+  quit     leave the game";
+
+struct Game<A: Algorithm> {
+    engine: A,
+    human: Team,
+    /// All positions of the game, starting with the initial one
+    history: Vec<Gamestate>,
+    moves: Vec<Move>,
+}
+
+impl<A: Algorithm> Game<A> {
+    fn state(&self) -> Gamestate {
+        *self.history.last().unwrap()
+    }
+
+    /// Returns: the team on turn, if it has no legal move and so lost the game
+    fn stuck_team(&self) -> Option<Team> {
+        let state = self.state();
+        let stuck = !state.game_over() && state.available_moves_current_player().is_empty();
+        stuck.then(|| state.current_player())
+    }
+
+    fn finished(&self) -> bool {
+        self.state().game_over() || self.stuck_team().is_some()
+    }
+
+    fn play(&mut self, mov: Move) {
+        let mut state = self.state();
+        state.apply_move(&mov);
+        self.history.push(state);
+        self.moves.push(mov);
+    }
+
+    /// Takes back moves until one of the human's moves was undone, so they are on turn again.
+    /// Returns: false, if there was no move to take back
+    fn undo(&mut self) -> bool {
+        if self.moves.is_empty() {
+            return false;
+        }
+        while let Some(mov) = self.moves.pop() {
+            self.history.pop();
+            println!("Took back {}", mov.notation(&self.state()));
+            if self.state().current_player() == self.human {
+                break;
+            }
+        }
+        true
+    }
+
+    fn engine_move(&mut self) {
+        let state = self.state();
+        let best = self.engine.best_move(state, state.current_player());
+        let mov = match watchdog::ensure_legal(&state, Some(best)) {
+            Some(mov) => mov,
+            None => return,
+        };
+        let mut line = format!("{} plays {}", self.engine.name(), mov.notation(&state));
+        if mov != best {
+            line.push_str(&format!(" instead of the illegal move {}", best));
+        } else if let Some(score) = self.engine.search_info().score {
+            line.push_str(&format!(" (score {})", score));
+        }
+        println!("{}", line);
+        self.play(mov);
+    }
+
+    fn print(&self) {
+        let state = self.state();
+        let mut highlighted = Bitboard::new();
+        if let Some(mov) = self.moves.last() {
+            highlighted.set_bit(mov.from);
+            highlighted.set_bit(mov.to);
+        }
+
+        println!();
+        println!("{}", state.board.render_highlighted(highlighted));
+        println!(
+            "Round {}  Ambers {}:{}  You play {}",
+            state.turn, state.ambers[0], state.ambers[1], self.human
+        );
+
+        if state.game_over() {
+            match state.winner() {
+                Some(team) if team == self.human => println!("Game over, you won!"),
+                Some(team) => println!("Game over, team {} won", team),
+                None => println!("Game over, it's a draw"),
+            }
+        }
+        match self.stuck_team() {
+            Some(team) if team == self.human => println!("Game over, you can't move and lost"),
+            Some(team) => println!("Game over, team {} can't move, you won!", team),
+            None => {}
+        }
+    }
+
+    /// Handles a line of input of the human player.
+    /// Returns: false, if the game should be left
+    fn handle_input(&mut self, input: &str) -> bool {
+        let state = self.state();
+        match input {
+            "" => {}
+            "quit" | "q" => return false,
+            "help" | "h" => println!("{}", HELP),
+            "fen" => println!("{}", state.to_fen()),
+            "moves" => {
+                let moves: Vec<String> = state
+                    .available_moves_current_player()
+                    .iter()
+                    .map(|mov| mov.notation(&state))
+                    .collect();
+                println!("{}", moves.join(" "));
+            }
+            "undo" => {
+                if self.undo() {
+                    self.print();
+                } else {
+                    println!("There is no move to take back");
+                }
+            }
+            "switch" => {
+                self.human = !self.human;
+                println!("You now play {}", self.human);
+            }
+            _ if self.finished() => println!("The game is over, try undo or quit"),
+            _ if state.current_player() != self.human => println!("It's not your turn"),
+            notation => match Move::from_notation(notation, &state) {
+                Ok(mov) => {
+                    self.play(mov);
+                    self.print();
+                }
+                Err(err) => println!("{}", err),
+            },
+        }
+        true
+    }
+
+    fn run(&mut self) {
+        println!("{}", HELP);
+        self.print();
+
+        let stdin = io::stdin();
+        loop {
+            let state = self.state();
+            if !self.finished() && state.current_player() != self.human {
+                self.engine_move();
+                self.print();
+                continue;
+            }
+
+            print!("> ");
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                break;
+            }
+            if !self.handle_input(line.trim()) {
+                break;
+            }
+        }
+    }
+}
+
+fn start<A: Algorithm>(engine: A, state: Gamestate, human: Team) {
+    let mut game = Game {
+        engine,
+        human,
+        history: vec![state],
+        moves: vec![],
+    };
+    game.run();
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let state = match (&args.fen, args.seed) {
+        (Some(fen), _) => Gamestate::load_fen(fen).unwrap_or_else(|err| {
+            eprintln!("Invalid FEN: {}", err);
+            std::process::exit(1);
+        }),
+        (None, Some(seed)) => Gamestate::new_random(&mut StdRng::seed_from_u64(seed)),
+        (None, None) => Gamestate::new_random(&mut rand::thread_rng()),
+    };
+    let human = if args.blue { Team::TWO } else { Team::ONE };
+
+    match args.algorithm {
+        AlgorithmKind::Minmax => start(MinMax::new(args.depth, EVAL_2603_1), state, human),
+        AlgorithmKind::Random => start(RandomPick, state, human),
+    }
+}
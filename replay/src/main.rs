@@ -0,0 +1,221 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use client::algorithms::registry::{self, AlgorithmSettings};
+use client::algorithms::Algorithm;
+use client::game::{Bitboard, Fen, Gamestate, IGamestate};
+use client::record::{GameRecord, RecordResult};
+
+/// Steps through a saved game record in the terminal
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Game record written by the client
+    #[clap(required_unless_present = "list")]
+    record: Option<PathBuf>,
+
+    /// Ply to start at
+    #[clap(long, default_value_t = 0)]
+    ply: usize,
+
+    /// Algorithm to ask for moves [default: minmax]
+    #[clap(short, long)]
+    algorithm: Option<String>,
+
+    /// Default depth of the search, when asking the engine for a move [default: 6]
+    #[clap(short, long)]
+    depth: Option<u8>,
+
+    /// Evaluation function [default: eval-2603-1]
+    #[clap(short, long)]
+    evaluation: Option<String>,
+
+    /// List the available algorithms and evaluation functions
+    #[clap(long)]
+    list: bool,
+}
+
+const HELP: &str = "Commands:
+  <enter>, n      next move
+  p               previous move
+  g <ply>         go to ply
+  s [depth]       show the engine's move at this ply
+  f               print the FEN of this ply
+  h               show this help
+  q               quit";
+
+struct Replay {
+    record: GameRecord,
+    positions: Vec<Gamestate>,
+    ply: usize,
+    algorithm: AlgorithmSettings,
+}
+
+impl Replay {
+    fn last_ply(&self) -> usize {
+        self.positions.len() - 1
+    }
+
+    fn print(&self) {
+        let state = &self.positions[self.ply];
+        let mut highlighted = Bitboard::new();
+
+        println!();
+        println!(
+            "Ply {}/{}  Round {}  Ambers {}:{}  {} vs. {}",
+            self.ply,
+            self.last_ply(),
+            state.turn,
+            state.ambers[0],
+            state.ambers[1],
+            self.record.red,
+            self.record.blue
+        );
+        if self.ply > 0 {
+            let recorded = &self.record.moves[self.ply - 1];
+            highlighted.set_bit(recorded.mov.from);
+            highlighted.set_bit(recorded.mov.to);
+
+            let mut line = format!(
+                "Last move: {}",
+                recorded.mov.notation(&self.positions[self.ply - 1])
+            );
+            if let Some(time) = recorded.time {
+                line.push_str(&format!("  time {:?}", time));
+            }
+            if let Some(score) = recorded.score {
+                line.push_str(&format!("  score {}", score));
+            }
+            println!("{}", line);
+        }
+
+        println!("{}", state.board.render_highlighted(highlighted));
+
+        if state.game_over() || self.ply == self.last_ply() {
+            let result = match self.record.result {
+                RecordResult::Winner(team) => format!("Team {} won", team),
+                RecordResult::Draw => "Draw".to_string(),
+                RecordResult::Unfinished => "Unfinished".to_string(),
+            };
+            match self.record.cause {
+                Some(cause) => println!("{} ({:?})", result, cause),
+                None => println!("{}", result),
+            }
+        }
+    }
+
+    /// Asks the engine for a move, at the given depth instead of the default one.
+    fn search(&self, depth: Option<u8>) {
+        let state = self.positions[self.ply];
+        if state.game_over() {
+            println!("The game is over at this ply");
+            return;
+        }
+        if state.available_moves_current_player().is_empty() {
+            println!("Team {} can't move at this ply", state.current_player());
+            return;
+        }
+
+        let settings = self.algorithm.clone().or(AlgorithmSettings {
+            depth,
+            ..AlgorithmSettings::default()
+        });
+        let mut algorithm = match settings.build() {
+            Ok(algorithm) => algorithm,
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+        let best = algorithm.best_move(state, state.current_player());
+        let score = algorithm
+            .search_info()
+            .score
+            .map(|score| score.to_string())
+            .unwrap_or_else(|| "?".to_string());
+        println!(
+            "{} plays {} (score {})",
+            algorithm.name(),
+            best.notation(&state),
+            score
+        );
+        if let Some(played) = self.record.moves.get(self.ply) {
+            println!("Played was {}", played.mov.notation(&state));
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.list {
+        print!("{}", registry::describe());
+        return;
+    }
+
+    let algorithm = AlgorithmSettings {
+        name: args.algorithm,
+        depth: args.depth,
+        evaluation: args.evaluation,
+        ..AlgorithmSettings::default()
+    };
+    if let Err(err) = algorithm.build() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+
+    let path = args.record.expect("required unless listing");
+    let record = GameRecord::read_from(&path).unwrap_or_else(|err| {
+        eprintln!("Failed to read {}: {}", path.display(), err);
+        std::process::exit(1);
+    });
+    let positions = record.positions();
+    let mut replay = Replay {
+        ply: args.ply.min(positions.len() - 1),
+        record,
+        positions,
+        algorithm,
+    };
+
+    println!("{}", HELP);
+    replay.print();
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) | (Some("n"), _) => {
+                if replay.ply < replay.last_ply() {
+                    replay.ply += 1;
+                }
+                replay.print();
+            }
+            (Some("p"), _) => {
+                replay.ply = replay.ply.saturating_sub(1);
+                replay.print();
+            }
+            (Some("g"), Some(ply)) => match ply.parse::<usize>() {
+                Ok(ply) if ply <= replay.last_ply() => {
+                    replay.ply = ply;
+                    replay.print();
+                }
+                _ => println!("Expected a ply between 0 and {}", replay.last_ply()),
+            },
+            (Some("s"), depth) => match depth.map(str::parse::<u8>) {
+                None => replay.search(None),
+                Some(Ok(depth)) if depth > 0 => replay.search(Some(depth)),
+                Some(_) => println!("Expected a positive depth"),
+            },
+            (Some("f"), _) => println!("{}", replay.positions[replay.ply].to_fen()),
+            (Some("q"), _) => break,
+            _ => println!("{}", HELP),
+        }
+    }
+}
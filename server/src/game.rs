@@ -0,0 +1,302 @@
+use std::io::Write;
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use client::game::{Fen, Gamestate, IGamestate, Move, MoveError, Team};
+use log::{info, warn};
+
+use crate::protocol::{self, Request};
+use crate::server::ServerConfig;
+use crate::xml::XmlNode;
+
+/// Why a team got its score, as in the result of the official server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreCause {
+    Regular,
+    Left,
+    RuleViolation,
+    SoftTimeout,
+    HardTimeout,
+}
+
+impl ScoreCause {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScoreCause::Regular => "REGULAR",
+            ScoreCause::Left => "LEFT",
+            ScoreCause::RuleViolation => "RULE_VIOLATION",
+            ScoreCause::SoftTimeout => "SOFT_TIMEOUT",
+            ScoreCause::HardTimeout => "HARD_TIMEOUT",
+        }
+    }
+}
+
+/// The result of a game played on the server.
+#[derive(Debug, Clone)]
+pub struct GameOutcome {
+    pub room_id: String,
+    pub start: Gamestate,
+    pub moves: Vec<Move>,
+    pub final_state: Gamestate,
+    pub winner: Option<Team>,
+    /// Cause of the score of each team, indexed by team
+    pub causes: [ScoreCause; 2],
+    /// Why the game ended irregularly, empty otherwise
+    pub reason: String,
+}
+
+impl GameOutcome {
+    /// Returns: the victory points of a team, 2 for a win and 1 for a draw
+    pub fn points(&self, team: Team) -> u8 {
+        match self.winner {
+            Some(winner) if winner == team => 2,
+            Some(_) => 0,
+            None => 1,
+        }
+    }
+}
+
+/// What the connection of a player reports to its game.
+#[derive(Debug)]
+pub(crate) enum Event {
+    Request(Request),
+    /// The player closed the connection or sent garbage
+    Left,
+}
+
+/// Where the connections report the events of their team.
+pub(crate) type Events = Sender<(Team, Event)>;
+
+/// A player's connection, which the game writes to.
+pub(crate) struct Seat {
+    stream: TcpStream,
+    connected: bool,
+}
+
+impl Seat {
+    pub(crate) fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            connected: true,
+        }
+    }
+
+    /// Sends a message to the player.
+    /// Returns: false, if the player is not connected anymore
+    fn send(&mut self, node: &XmlNode) -> bool {
+        if self.connected {
+            let written = write!(self.stream, "{}", node).and_then(|_| self.stream.flush());
+            if let Err(err) = written {
+                warn!("Failed to write to player: {}", err);
+                self.connected = false;
+            }
+        }
+        self.connected
+    }
+
+    fn close(&mut self) {
+        if self.connected {
+            let _ = write!(self.stream, "</protocol>");
+            let _ = self.stream.shutdown(Shutdown::Both);
+            self.connected = false;
+        }
+    }
+}
+
+pub(crate) struct Game {
+    room_id: String,
+    start: Gamestate,
+    state: Gamestate,
+    moves: Vec<Move>,
+    seats: [Seat; 2],
+    events: Receiver<(Team, Event)>,
+}
+
+/// How a game ended, before the scores are given out
+struct Ending {
+    loser: Team,
+    cause: ScoreCause,
+    reason: String,
+}
+
+impl Game {
+    pub(crate) fn new(
+        room_id: String,
+        start: Gamestate,
+        seats: [Seat; 2],
+        events: Receiver<(Team, Event)>,
+    ) -> Self {
+        Self {
+            room_id,
+            start,
+            state: start,
+            moves: vec![],
+            seats,
+            events,
+        }
+    }
+
+    fn broadcast(&mut self, node: &XmlNode) -> Result<(), Ending> {
+        for team in [Team::ONE, Team::TWO] {
+            if !self.seats[team as usize].send(node) {
+                return Err(left(team));
+            }
+        }
+        Ok(())
+    }
+
+    /// Plays the game until it is over or one of the players breaks the rules.
+    pub(crate) fn run(mut self, config: &ServerConfig) -> GameOutcome {
+        info!("Starting game {} at {}", self.room_id, self.start.to_fen());
+        let ending = self.play(config);
+        let outcome = self.outcome(ending);
+        info!(
+            "Game {} ended after {} moves, winner {:?} {:?}",
+            self.room_id,
+            self.moves.len(),
+            outcome.winner,
+            outcome.causes
+        );
+
+        let result = protocol::result(&self.room_id, &outcome);
+        let left = protocol::left(&self.room_id);
+        for seat in self.seats.iter_mut() {
+            seat.send(&result);
+            seat.send(&left);
+            seat.close();
+        }
+        outcome
+    }
+
+    fn play(&mut self, config: &ServerConfig) -> Result<(), Ending> {
+        for team in [Team::ONE, Team::TWO] {
+            let welcome = protocol::welcome(&self.room_id, team);
+            if !self.seats[team as usize].send(&welcome) {
+                return Err(left(team));
+            }
+        }
+
+        loop {
+            let memento = protocol::memento(&self.room_id, &self.state, self.moves.last().copied());
+            self.broadcast(&memento)?;
+            if self.state.game_over() {
+                return Ok(());
+            }
+
+            let team = self.state.current_player();
+            // Like in a game against the engine, a team that can't move loses
+            if self.state.available_moves_current_player().is_empty() {
+                return Err(Ending {
+                    loser: team,
+                    cause: ScoreCause::Regular,
+                    reason: format!("Team {} can't move", team),
+                });
+            }
+            if !self.seats[team as usize].send(&protocol::move_request(&self.room_id)) {
+                return Err(left(team));
+            }
+            let (from, to, took) = self.receive_move(team, config)?;
+            if took > config.move_time {
+                return Err(Ending {
+                    loser: team,
+                    cause: ScoreCause::SoftTimeout,
+                    reason: format!("Took {:?} for a move", took),
+                });
+            }
+
+            let piece = self
+                .state
+                .board
+                .piece_at(from)
+                .map(|piece| piece.piece_type());
+            let applied = match piece {
+                Some(piece) => {
+                    let mov = Move { from, to, piece };
+                    self.state.try_apply_move(&mov).map(|_| mov)
+                }
+                None => Err(MoveError::NoPieceAt(from)),
+            };
+            match applied {
+                Ok(mov) => self.moves.push(mov),
+                Err(err) => {
+                    return Err(Ending {
+                        loser: team,
+                        cause: ScoreCause::RuleViolation,
+                        reason: err.to_string(),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Waits for the move of the team on turn, which has to arrive before the hard timeout.
+    fn receive_move(
+        &mut self,
+        team: Team,
+        config: &ServerConfig,
+    ) -> Result<(u8, u8, Duration), Ending> {
+        let requested = Instant::now();
+        loop {
+            let remaining = config.hard_timeout.saturating_sub(requested.elapsed());
+            let (sender, event) = match self.events.recv_timeout(remaining) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(Ending {
+                        loser: team,
+                        cause: ScoreCause::HardTimeout,
+                        reason: format!("No move within {:?}", config.hard_timeout),
+                    })
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(left(team)),
+            };
+            match event {
+                Event::Left => return Err(left(sender)),
+                Event::Request(Request::Move { room_id, from, to }) if room_id == self.room_id => {
+                    if sender != team {
+                        return Err(Ending {
+                            loser: sender,
+                            cause: ScoreCause::RuleViolation,
+                            reason: "Sent a move while not on turn".to_string(),
+                        });
+                    }
+                    return Ok((from, to, requested.elapsed()));
+                }
+                Event::Request(request) => {
+                    warn!(
+                        "Ignoring request of team {} in game {}: {:?}",
+                        sender, self.room_id, request
+                    )
+                }
+            }
+        }
+    }
+
+    fn outcome(&self, ending: Result<(), Ending>) -> GameOutcome {
+        let mut causes = [ScoreCause::Regular; 2];
+        let (winner, reason) = match ending {
+            Ok(()) => (self.state.winner(), String::new()),
+            Err(ending) => {
+                causes[ending.loser as usize] = ending.cause;
+                (Some(!ending.loser), ending.reason)
+            }
+        };
+        GameOutcome {
+            room_id: self.room_id.clone(),
+            start: self.start,
+            moves: self.moves.clone(),
+            final_state: self.state,
+            winner,
+            causes,
+            reason,
+        }
+    }
+}
+
+fn left(team: Team) -> Ending {
+    Ending {
+        loser: team,
+        cause: ScoreCause::Left,
+        reason: format!("Team {} left the game", team),
+    }
+}
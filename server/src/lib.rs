@@ -0,0 +1,7 @@
+pub mod game;
+pub mod protocol;
+pub mod server;
+pub mod xml;
+
+pub use game::{GameOutcome, ScoreCause};
+pub use server::{Reservation, Server, ServerConfig};
@@ -0,0 +1,112 @@
+use std::io::Write;
+use std::time::Duration;
+
+use chrono::Local;
+use clap::Parser;
+use client::game::{Fen, Gamestate, Team};
+use client::logging::LOG_FILTER_VAR;
+use env_logger::{Builder, Target};
+use log::{info, LevelFilter};
+use server::{Server, ServerConfig};
+
+/// Local game server for Ostseeschach, speaking the protocol of the Software Challenge
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Address to listen on
+    #[clap(long, default_value = "localhost")]
+    host: String,
+
+    /// Port to listen on
+    #[clap(short, long, default_value_t = 13050)]
+    port: u16,
+
+    /// Time per move in milliseconds, before a player loses by a soft timeout
+    #[clap(long, default_value_t = 2000)]
+    move_time: u64,
+
+    /// Time in milliseconds after which the server stops waiting for a move
+    #[clap(long, default_value_t = 10000)]
+    hard_timeout: u64,
+
+    /// Prepare this many games, which can be joined by the printed reservation codes
+    #[clap(long, default_value_t = 0)]
+    prepare: usize,
+
+    /// Fen String of the starting position of prepared games, random otherwise
+    #[clap(short, long)]
+    fen: Option<String>,
+
+    /// Stop after this many games, run forever otherwise
+    #[clap(short, long)]
+    games: Option<usize>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    Builder::new()
+        .parse_env(LOG_FILTER_VAR)
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "{} [{}] - {}",
+                Local::now().format("%Y-%m-%dT%H:%M:%S"),
+                record.level(),
+                record.args()
+            )
+        })
+        .filter_level(LevelFilter::Info)
+        .target(Target::Stdout)
+        .init();
+
+    let start = args
+        .fen
+        .as_ref()
+        .map(|fen| Gamestate::load_fen(fen).expect("Please input a valid FEN"));
+    let config = ServerConfig {
+        move_time: Duration::from_millis(args.move_time),
+        hard_timeout: Duration::from_millis(args.hard_timeout),
+    };
+    let server =
+        Server::start((args.host.as_str(), args.port), config).expect("Failed to start the server");
+
+    for _ in 0..args.prepare {
+        let reservation = server.prepare_game(start);
+        info!(
+            "Prepared room {} with reservations {} (ONE) and {} (TWO)",
+            reservation.room_id, reservation.codes[0], reservation.codes[1]
+        );
+    }
+
+    let mut wins = [0; 2];
+    let mut played = 0;
+    while args.games != Some(played) {
+        let outcome = server.wait_for_result();
+        played += 1;
+        if let Some(team) = outcome.winner {
+            wins[team as usize] += 1;
+        }
+        info!(
+            "Game {} ({}): {}  ambers {}:{}  {:?} {}",
+            played,
+            outcome.room_id,
+            match outcome.winner {
+                Some(Team::ONE) => "ONE won",
+                Some(Team::TWO) => "TWO won",
+                None => "draw",
+            },
+            outcome.final_state.ambers[0],
+            outcome.final_state.ambers[1],
+            outcome.causes,
+            outcome.reason
+        );
+    }
+    info!(
+        "Played {} games: ONE won {}, TWO won {}, {} draws",
+        played,
+        wins[0],
+        wins[1],
+        played - wins[0] - wins[1]
+    );
+}
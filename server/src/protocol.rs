@@ -0,0 +1,325 @@
+use client::bridge::{position_to_xy, xy_to_position};
+use client::game::{Board, Gamestate, Move, Piece, PieceType, Team, MAX_AMBERS};
+
+use crate::game::GameOutcome;
+use crate::xml::XmlNode;
+
+/// Game type of Ostseeschach, which clients may name when joining
+pub const GAME_TYPE: &str = "swc_2022_ostseeschach";
+
+/// Requests a client sends to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Join any open game, optionally of the given game type
+    Join {
+        game_type: Option<String>,
+    },
+    JoinRoom {
+        room_id: String,
+    },
+    JoinPrepared {
+        reservation_code: String,
+    },
+    /// A move, with the positions converted to our board
+    Move {
+        room_id: String,
+        from: u8,
+        to: u8,
+    },
+    /// A request this server doesn't understand
+    Unknown(XmlNode),
+}
+
+fn coordinates_node(name: &str, pos: u8) -> XmlNode {
+    let (x, y) = position_to_xy(pos);
+    XmlNode::new(name).attribute("x", x).attribute("y", y)
+}
+
+fn parse_coordinates(node: Option<&XmlNode>) -> Option<u8> {
+    let node = node?;
+    let x = node.get_attribute("x")?.parse().ok()?;
+    let y = node.get_attribute("y")?.parse().ok()?;
+    xy_to_position(x, y)
+}
+
+impl Request {
+    pub fn parse(node: XmlNode) -> Self {
+        let parsed = match node.name.as_str() {
+            "join" => Some(Request::Join {
+                game_type: node.get_attribute("gameType").map(str::to_string),
+            }),
+            "joinRoom" => node.get_attribute("roomId").map(|id| Request::JoinRoom {
+                room_id: id.to_string(),
+            }),
+            "joinPrepared" => {
+                node.get_attribute("reservationCode")
+                    .map(|code| Request::JoinPrepared {
+                        reservation_code: code.to_string(),
+                    })
+            }
+            "room" => Self::parse_move(&node),
+            _ => None,
+        };
+        parsed.unwrap_or(Request::Unknown(node))
+    }
+
+    fn parse_move(node: &XmlNode) -> Option<Self> {
+        let data = node.get_child("data")?;
+        if data.get_attribute("class") != Some("move") {
+            return None;
+        }
+        Some(Request::Move {
+            room_id: node.get_attribute("roomId")?.to_string(),
+            from: parse_coordinates(data.get_child("from"))?,
+            to: parse_coordinates(data.get_child("to"))?,
+        })
+    }
+
+    /// Returns: the request as sent by a client
+    pub fn to_xml(&self) -> XmlNode {
+        match self {
+            Request::Join { game_type } => {
+                let mut node = XmlNode::new("join");
+                if let Some(game_type) = game_type {
+                    node = node.attribute("gameType", game_type);
+                }
+                node
+            }
+            Request::JoinRoom { room_id } => XmlNode::new("joinRoom").attribute("roomId", room_id),
+            Request::JoinPrepared { reservation_code } => {
+                XmlNode::new("joinPrepared").attribute("reservationCode", reservation_code)
+            }
+            Request::Move { room_id, from, to } => room(
+                room_id,
+                XmlNode::new("data")
+                    .attribute("class", "move")
+                    .child(coordinates_node("from", *from))
+                    .child(coordinates_node("to", *to)),
+            ),
+            Request::Unknown(node) => node.clone(),
+        }
+    }
+}
+
+fn room(room_id: &str, data: XmlNode) -> XmlNode {
+    XmlNode::new("room")
+        .attribute("roomId", room_id)
+        .child(data)
+}
+
+fn data(class: &str) -> XmlNode {
+    XmlNode::new("data").attribute("class", class)
+}
+
+pub fn joined(room_id: &str) -> XmlNode {
+    XmlNode::new("joined").attribute("roomId", room_id)
+}
+
+pub fn left(room_id: &str) -> XmlNode {
+    XmlNode::new("left").attribute("roomId", room_id)
+}
+
+pub fn error(message: &str) -> XmlNode {
+    XmlNode::new("errorpacket").attribute("message", message)
+}
+
+pub fn welcome(room_id: &str, team: Team) -> XmlNode {
+    let color = match team {
+        Team::ONE => "one",
+        Team::TWO => "two",
+    };
+    room(room_id, data("welcomeMessage").attribute("color", color))
+}
+
+pub fn memento(room_id: &str, state: &Gamestate, last_move: Option<Move>) -> XmlNode {
+    room(
+        room_id,
+        data("memento").child(state_to_xml(state, last_move)),
+    )
+}
+
+pub fn move_request(room_id: &str) -> XmlNode {
+    room(room_id, data("moveRequest"))
+}
+
+pub fn result(room_id: &str, outcome: &GameOutcome) -> XmlNode {
+    let fragment = |name: &str, aggregation: &str| {
+        XmlNode::new("fragment")
+            .attribute("name", name)
+            .child(XmlNode::new("aggregation").content(aggregation))
+            .child(XmlNode::new("relevantForRanking").content(true))
+    };
+    let definition = XmlNode::new("definition")
+        .child(fragment("Siegpunkte", "SUM"))
+        .child(fragment("∅ Bernsteine", "AVERAGE"));
+
+    let scores = [Team::ONE, Team::TWO].into_iter().map(|team| {
+        let score = XmlNode::new("score")
+            .attribute("cause", outcome.causes[team as usize].as_str())
+            .attribute("reason", &outcome.reason)
+            .child(XmlNode::new("part").content(outcome.points(team)))
+            .child(XmlNode::new("part").content(outcome.final_state.ambers[team as usize]));
+        XmlNode::new("entry")
+            .child(
+                XmlNode::new("player")
+                    .attribute("name", format!("Player {}", team as usize + 1))
+                    .attribute("team", team),
+            )
+            .child(score)
+    });
+
+    let mut node = data("result")
+        .child(definition)
+        .child(XmlNode::new("scores").children(scores));
+    if let Some(winner) = outcome.winner {
+        node = node.child(XmlNode::new("winner").attribute("team", winner));
+    }
+    room(room_id, node)
+}
+
+/// Converts a gamestate into the `state` element of a memento.
+pub fn state_to_xml(state: &Gamestate, last_move: Option<Move>) -> XmlNode {
+    let pieces = (0..64u8).filter_map(|pos| {
+        let piece = state.board.piece_at(pos)?;
+        Some(
+            XmlNode::new("entry")
+                .child(coordinates_node("coordinates", pos))
+                .child(
+                    XmlNode::new("piece")
+                        .attribute("type", piece.piece_type())
+                        .attribute("team", piece.team())
+                        .attribute("count", if piece.is_stacked() { 2 } else { 1 }),
+                ),
+        )
+    });
+    let ambers = [Team::ONE, Team::TWO].into_iter().map(|team| {
+        XmlNode::new("entry")
+            .child(XmlNode::new("team").content(team))
+            .child(XmlNode::new("int").content(state.ambers[team as usize]))
+    });
+
+    let mut node = XmlNode::new("state")
+        .attribute("class", "state")
+        .attribute("turn", state.turn)
+        .child(XmlNode::new("startTeam").content(Team::ONE))
+        .child(XmlNode::new("board").child(XmlNode::new("pieces").children(pieces)))
+        .child(
+            XmlNode::new("ambers")
+                .attribute("enum-type", "team")
+                .children(ambers),
+        );
+    if let Some(mov) = last_move {
+        node = node.child(
+            XmlNode::new("lastMove")
+                .child(coordinates_node("from", mov.from))
+                .child(coordinates_node("to", mov.to)),
+        );
+    }
+    node
+}
+
+/// Converts the `state` element of a memento back into a gamestate.
+/// Returns: None, if the element is incomplete or malformed, or a team has more than
+/// [`MAX_AMBERS`] ambers
+pub fn state_from_xml(node: &XmlNode) -> Option<Gamestate> {
+    let turn = node.get_attribute("turn")?.parse().ok()?;
+
+    let mut board = Board::empty();
+    for entry in node
+        .get_child("board")?
+        .get_child("pieces")?
+        .children
+        .iter()
+    {
+        let pos = parse_coordinates(entry.get_child("coordinates"))?;
+        let piece = entry.get_child("piece")?;
+        let piece_type: PieceType = piece.get_attribute("type")?.parse().ok()?;
+        let team: Team = piece.get_attribute("team")?.parse().ok()?;
+        let count: u8 = piece.get_attribute("count")?.parse().ok()?;
+        board.set_piece(pos, Piece::new(piece_type, team, count > 1));
+    }
+
+    let mut ambers = [0; 2];
+    for entry in node.get_child("ambers")?.children.iter() {
+        let team: Team = entry.get_child("team")?.content.parse().ok()?;
+        let count: u8 = entry.get_child("int")?.content.parse().ok()?;
+        if count > MAX_AMBERS {
+            return None;
+        }
+        ambers[team as usize] = count;
+    }
+    Some(Gamestate::new_with(board, turn, ambers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::game::IGamestate;
+    use rand::prelude::SliceRandom;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut state = Gamestate::new_random(&mut rng);
+        while !state.game_over() {
+            let node = state_to_xml(&state, None);
+            assert_eq!(state_from_xml(&node), Some(state));
+
+            let moves = state.available_moves_current_player();
+            state.apply_move(moves.choose(&mut rng).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_state_ambers_out_of_range() {
+        let state = Gamestate::new_random(&mut StdRng::seed_from_u64(3));
+        let with_ambers = |count: &str| {
+            let mut node = state_to_xml(&state, None);
+            let ambers = node
+                .children
+                .iter_mut()
+                .find(|child| child.name == "ambers")
+                .unwrap();
+            ambers.children[1].children[1].content = count.to_string();
+            node
+        };
+
+        let read = state_from_xml(&with_ambers("3")).unwrap();
+        assert_eq!(read.ambers, [0, 3]);
+        assert_eq!(state_from_xml(&with_ambers("4")), None);
+        assert_eq!(state_from_xml(&with_ambers("255")), None);
+    }
+
+    #[test]
+    fn test_requests() {
+        let requests = [
+            Request::Join { game_type: None },
+            Request::Join {
+                game_type: Some(GAME_TYPE.to_string()),
+            },
+            Request::JoinRoom {
+                room_id: "abc".to_string(),
+            },
+            Request::JoinPrepared {
+                reservation_code: "123".to_string(),
+            },
+            Request::Move {
+                room_id: "abc".to_string(),
+                from: 7,
+                to: 14,
+            },
+        ];
+        for request in requests {
+            assert_eq!(Request::parse(request.to_xml()), request);
+        }
+
+        let out_of_board = XmlNode::new("room").attribute("roomId", "abc").child(
+            data("move")
+                .child(XmlNode::new("from").attribute("x", 8).attribute("y", 0))
+                .child(XmlNode::new("to").attribute("x", 1).attribute("y", 0)),
+        );
+        assert!(matches!(Request::parse(out_of_board), Request::Unknown(_)));
+    }
+}
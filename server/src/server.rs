@@ -0,0 +1,536 @@
+use std::collections::HashMap;
+use std::io::{self, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use client::game::{Gamestate, Team};
+use log::{info, warn};
+use rand::Rng;
+
+use crate::game::{Event, Events, Game, GameOutcome, Seat};
+use crate::protocol::{self, Request, GAME_TYPE};
+use crate::xml::{Token, XmlReader};
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Time a player has for a move, before losing by a soft timeout
+    pub move_time: Duration,
+    /// Time after which the server stops waiting for a move, the player loses by a hard timeout
+    pub hard_timeout: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            move_time: Duration::from_secs(2),
+            hard_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Codes for joining a prepared game with `joinPrepared`, indexed by team.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reservation {
+    pub room_id: String,
+    pub codes: [String; 2],
+}
+
+/// A room waiting for its players.
+struct Room {
+    /// Rooms of prepared games can only be joined by reservation
+    prepared: bool,
+    start: Option<Gamestate>,
+    seats: [Option<Seat>; 2],
+    /// Seats promised to a player, who is about to be told that they joined
+    claimed: [bool; 2],
+    events: Events,
+    receiver: Receiver<(Team, Event)>,
+}
+
+impl Room {
+    fn new(prepared: bool, start: Option<Gamestate>) -> Self {
+        let (events, receiver) = mpsc::channel();
+        Self {
+            prepared,
+            start,
+            seats: [None, None],
+            claimed: [false, false],
+            events,
+            receiver,
+        }
+    }
+
+    fn free_seat(&self) -> Option<Team> {
+        [Team::ONE, Team::TWO]
+            .into_iter()
+            .find(|team| !self.claimed[*team as usize])
+    }
+
+    fn full(&self) -> bool {
+        self.seats.iter().all(Option::is_some)
+    }
+}
+
+#[derive(Default)]
+struct Lobby {
+    rooms: HashMap<String, Room>,
+    /// Room and team of each reservation code
+    reservations: HashMap<String, (String, Team)>,
+}
+
+fn random_id() -> String {
+    format!("{:016x}", rand::thread_rng().gen::<u64>())
+}
+
+struct Shared {
+    config: ServerConfig,
+    lobby: Mutex<Lobby>,
+    results: Mutex<Sender<GameOutcome>>,
+}
+
+impl Shared {
+    /// Seats a player in the room requested by `request` and starts the game once the room is full.
+    /// The lobby isn't locked while the player is told that they joined, and the seat and
+    /// reservation are only taken once that succeeded.
+    /// Returns: the room and team of the player, and where to report its requests to
+    fn join(
+        self: &Arc<Self>,
+        request: Request,
+        mut stream: TcpStream,
+    ) -> Result<(String, Team, Events), String> {
+        let (room_id, team, reservation_code) = self.claim_seat(request)?;
+
+        if let Err(err) =
+            stream.write_all(format!("<protocol>{}", protocol::joined(&room_id)).as_bytes())
+        {
+            let mut lobby = self.lobby.lock().unwrap();
+            let room = lobby.rooms.get_mut(&room_id).unwrap();
+            room.claimed[team as usize] = false;
+            // Don't leave an empty room behind, which nobody knows of
+            if !room.prepared && !room.claimed.contains(&true) {
+                lobby.rooms.remove(&room_id);
+            }
+            return Err(err.to_string());
+        }
+
+        let mut lobby = self.lobby.lock().unwrap();
+        if let Some(code) = reservation_code {
+            lobby.reservations.remove(&code);
+        }
+        let room = lobby.rooms.get_mut(&room_id).unwrap();
+        room.seats[team as usize] = Some(Seat::new(stream));
+        let events = room.events.clone();
+        info!("Team {} joined room {}", team, room_id);
+
+        if room.full() {
+            let room = lobby.rooms.remove(&room_id).unwrap();
+            self.start_game(room_id.clone(), room);
+        }
+        Ok((room_id, team, events))
+    }
+
+    /// Finds the seat requested by `request` and claims it, opening a new room if needed.
+    /// Returns: the room and team of the seat, and the reservation code it was claimed with
+    fn claim_seat(&self, request: Request) -> Result<(String, Team, Option<String>), String> {
+        let mut lobby = self.lobby.lock().unwrap();
+        let (room_id, team, reservation_code) = match request {
+            Request::Join { game_type } => {
+                if let Some(game_type) = game_type.filter(|game_type| game_type != GAME_TYPE) {
+                    return Err(format!("Unknown game type {}", game_type));
+                }
+                let open = lobby
+                    .rooms
+                    .iter()
+                    .filter(|(_, room)| !room.prepared)
+                    .find_map(|(id, room)| Some((id.clone(), room.free_seat()?)));
+                let (room_id, team) = match open {
+                    Some(open) => open,
+                    None => {
+                        let room_id = random_id();
+                        lobby.rooms.insert(room_id.clone(), Room::new(false, None));
+                        (room_id, Team::ONE)
+                    }
+                };
+                (room_id, team, None)
+            }
+            Request::JoinRoom { room_id } => {
+                let room = lobby
+                    .rooms
+                    .get(&room_id)
+                    .ok_or_else(|| format!("There is no room {}", room_id))?;
+                if room.prepared {
+                    return Err(format!(
+                        "Room {} can only be joined by reservation",
+                        room_id
+                    ));
+                }
+                let team = room
+                    .free_seat()
+                    .ok_or_else(|| format!("Room {} is full", room_id))?;
+                (room_id, team, None)
+            }
+            Request::JoinPrepared { reservation_code } => {
+                let (room_id, team) = lobby
+                    .reservations
+                    .get(&reservation_code)
+                    .cloned()
+                    .ok_or_else(|| format!("Unknown reservation code {}", reservation_code))?;
+                if lobby.rooms[&room_id].claimed[team as usize] {
+                    return Err(format!(
+                        "Reservation code {} is already being used",
+                        reservation_code
+                    ));
+                }
+                (room_id, team, Some(reservation_code))
+            }
+            request => return Err(format!("Expected to join a game first, got {:?}", request)),
+        };
+        lobby.rooms.get_mut(&room_id).unwrap().claimed[team as usize] = true;
+        Ok((room_id, team, reservation_code))
+    }
+
+    fn start_game(self: &Arc<Self>, room_id: String, room: Room) {
+        let start = room
+            .start
+            .unwrap_or_else(|| Gamestate::new_random(&mut rand::thread_rng()));
+        let [one, two] = room.seats;
+        let game = Game::new(room_id, start, [one.unwrap(), two.unwrap()], room.receiver);
+
+        let shared = Arc::clone(self);
+        thread::spawn(move || {
+            let outcome = game.run(&shared.config);
+            let _ = shared.results.lock().unwrap().send(outcome);
+        });
+    }
+
+    fn handle_connection(self: &Arc<Self>, stream: TcpStream) -> io::Result<()> {
+        let peer = stream.peer_addr()?;
+        let mut reader = XmlReader::new(BufReader::new(stream.try_clone()?));
+        match reader.next_token()? {
+            Some(Token::Start {
+                node,
+                self_closing: false,
+            }) if node.name == "protocol" => {}
+            token => {
+                warn!("{} didn't open the protocol, but sent {:?}", peer, token);
+                return Ok(());
+            }
+        }
+
+        let request = match reader.next_element()? {
+            Some(node) => Request::parse(node),
+            None => return Ok(()),
+        };
+        let (room_id, team, events) = match self.join(request, stream.try_clone()?) {
+            Ok(joined) => joined,
+            Err(message) => {
+                warn!("{} failed to join: {}", peer, message);
+                let mut stream = stream;
+                write!(stream, "<protocol>{}</protocol>", protocol::error(&message))?;
+                return Ok(());
+            }
+        };
+
+        loop {
+            let event = match reader.next_element() {
+                Ok(Some(node)) => Event::Request(Request::parse(node)),
+                Ok(None) => Event::Left,
+                Err(err) => {
+                    warn!(
+                        "Invalid XML from team {} in room {}: {}",
+                        team, room_id, err
+                    );
+                    Event::Left
+                }
+            };
+            let left = matches!(event, Event::Left);
+            // The game is gone once it has ended
+            if events.send((team, event)).is_err() || left {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// A local stand-in for the game server of the Software Challenge, which speaks the same
+/// XML protocol and plays Ostseeschach by the rules of our [`Gamestate`].
+///
+/// Players can join any open game, a room by its id or a prepared game by reservation.
+/// The server runs in the background until the process ends.
+pub struct Server {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    results: Receiver<GameOutcome>,
+}
+
+impl Server {
+    /// Starts listening on the given address, use port 0 to pick any free port.
+    pub fn start<A: ToSocketAddrs>(addr: A, config: ServerConfig) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let (sender, results) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            config,
+            lobby: Mutex::new(Lobby::default()),
+            results: Mutex::new(sender),
+        });
+
+        let accepting = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        warn!("Failed to accept a connection: {}", err);
+                        continue;
+                    }
+                };
+                let shared = Arc::clone(&accepting);
+                thread::spawn(move || {
+                    if let Err(err) = shared.handle_connection(stream) {
+                        warn!("Connection failed: {}", err);
+                    }
+                });
+            }
+        });
+        info!("Listening on {}", addr);
+
+        Ok(Self {
+            addr,
+            shared,
+            results,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Prepares a game, which starts at the given gamestate or a random one.
+    pub fn prepare_game(&self, start: Option<Gamestate>) -> Reservation {
+        let mut lobby = self.shared.lobby.lock().unwrap();
+        let room_id = random_id();
+        let codes = [random_id(), random_id()];
+        for team in [Team::ONE, Team::TWO] {
+            lobby
+                .reservations
+                .insert(codes[team as usize].clone(), (room_id.clone(), team));
+        }
+        lobby.rooms.insert(room_id.clone(), Room::new(true, start));
+        Reservation { room_id, codes }
+    }
+
+    /// Waits for the next game to end.
+    /// Returns: None, if no game ended within the timeout
+    pub fn next_result(&self, timeout: Duration) -> Option<GameOutcome> {
+        match self.results.recv_timeout(timeout) {
+            Ok(outcome) => Some(outcome),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    /// Waits for the next game to end.
+    pub fn wait_for_result(&self) -> GameOutcome {
+        // The server keeps a sender itself, so this can't fail
+        self.results.recv().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::ScoreCause;
+    use crate::protocol::state_from_xml;
+    use crate::xml::XmlNode;
+    use client::game::{Fen, IGamestate};
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Connects to the server and joins with the given request.
+    fn connect(server: &Server, request: Request) -> (TcpStream, XmlReader<BufReader<TcpStream>>) {
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        write!(stream, "<protocol>{}", request.to_xml()).unwrap();
+        let mut reader = XmlReader::new(BufReader::new(stream.try_clone().unwrap()));
+        assert!(matches!(
+            reader.next_token().unwrap(),
+            Some(Token::Start { node, .. }) if node.name == "protocol"
+        ));
+        (stream, reader)
+    }
+
+    /// Plays a game by sending the first legal move or the one given by `choose`.
+    /// Returns: the messages received from the server
+    fn play(
+        mut stream: TcpStream,
+        mut reader: XmlReader<BufReader<TcpStream>>,
+        choose: impl Fn(&Gamestate) -> Option<(u8, u8)>,
+    ) -> Vec<XmlNode> {
+        let mut received = vec![];
+        let mut state = None;
+        while let Some(node) = reader.next_element().unwrap() {
+            let data = node.get_child("data").cloned().unwrap_or_default();
+            match data.get_attribute("class") {
+                Some("memento") => state = state_from_xml(data.get_child("state").unwrap()),
+                Some("moveRequest") => {
+                    let state: Gamestate = state.unwrap();
+                    let (from, to) = choose(&state).unwrap_or_else(|| {
+                        let mov = state.available_moves_current_player()[0];
+                        (mov.from, mov.to)
+                    });
+                    let request = Request::Move {
+                        room_id: node.get_attribute("roomId").unwrap().to_string(),
+                        from,
+                        to,
+                    };
+                    write!(stream, "{}", request.to_xml()).unwrap();
+                }
+                _ => {}
+            }
+            received.push(node);
+        }
+        received
+    }
+
+    fn spawn_player(server: &Server, request: Request) -> thread::JoinHandle<Vec<XmlNode>> {
+        let (stream, reader) = connect(server, request);
+        thread::spawn(move || play(stream, reader, |_| None))
+    }
+
+    #[test]
+    fn test_join_any() {
+        let server = Server::start("localhost:0", ServerConfig::default()).unwrap();
+        let one = spawn_player(&server, Request::Join { game_type: None });
+        let two = spawn_player(
+            &server,
+            Request::Join {
+                game_type: Some(GAME_TYPE.to_string()),
+            },
+        );
+
+        let outcome = server.next_result(TIMEOUT).unwrap();
+        assert_eq!(outcome.causes, [ScoreCause::Regular; 2]);
+        assert!(outcome.final_state.game_over());
+        assert_eq!(outcome.winner, outcome.final_state.winner());
+
+        for (team, player) in [(Team::ONE, one), (Team::TWO, two)] {
+            let received = player.join().unwrap();
+            let names: Vec<&str> = received.iter().map(|node| node.name.as_str()).collect();
+            assert_eq!(names.first(), Some(&"joined"));
+            assert_eq!(names.last(), Some(&"left"));
+            let welcome = received[1].get_child("data").unwrap();
+            assert_eq!(
+                welcome.get_attribute("color"),
+                Some(if team == Team::ONE { "one" } else { "two" })
+            );
+            let result = received[received.len() - 2].get_child("data").unwrap();
+            assert_eq!(result.get_attribute("class"), Some("result"));
+        }
+    }
+
+    #[test]
+    fn test_prepared_game() {
+        let server = Server::start("localhost:0", ServerConfig::default()).unwrap();
+        let start = Gamestate::load_fen("v1 7m/8/8/8/8/8/8/R7 r 0 0/0").unwrap();
+        let reservation = server.prepare_game(Some(start));
+
+        // Prepared rooms can't be joined without reservation
+        let (_, mut reader) = connect(
+            &server,
+            Request::JoinRoom {
+                room_id: reservation.room_id.clone(),
+            },
+        );
+        let error = reader.next_element().unwrap().unwrap();
+        assert_eq!(error.name, "errorpacket");
+
+        let two = spawn_player(
+            &server,
+            Request::JoinPrepared {
+                reservation_code: reservation.codes[1].clone(),
+            },
+        );
+        let (stream, reader) = connect(
+            &server,
+            Request::JoinPrepared {
+                reservation_code: reservation.codes[0].clone(),
+            },
+        );
+        // The Robbe on a1 can't move to a2
+        let one = thread::spawn(move || play(stream, reader, |_| Some((0, 1))));
+
+        let outcome = server.next_result(TIMEOUT).unwrap();
+        assert_eq!(outcome.room_id, reservation.room_id);
+        assert_eq!(outcome.start, start);
+        assert_eq!(outcome.winner, Some(Team::TWO));
+        assert_eq!(
+            outcome.causes,
+            [ScoreCause::RuleViolation, ScoreCause::Regular]
+        );
+        one.join().unwrap();
+        two.join().unwrap();
+
+        // Reservations can only be used once
+        let (_, mut reader) = connect(
+            &server,
+            Request::JoinPrepared {
+                reservation_code: reservation.codes[0].clone(),
+            },
+        );
+        assert_eq!(reader.next_element().unwrap().unwrap().name, "errorpacket");
+    }
+
+    #[test]
+    fn test_stuck_team() {
+        let server = Server::start("localhost:0", ServerConfig::default()).unwrap();
+        // Red has no pieces left, so it can't move
+        let start = Gamestate::load_fen("v1 7m/8/8/8/8/8/8/8 r 0 0/0").unwrap();
+        let reservation = server.prepare_game(Some(start));
+        let players: Vec<_> = reservation
+            .codes
+            .iter()
+            .map(|code| {
+                spawn_player(
+                    &server,
+                    Request::JoinPrepared {
+                        reservation_code: code.clone(),
+                    },
+                )
+            })
+            .collect();
+
+        let outcome = server.next_result(TIMEOUT).unwrap();
+        assert_eq!(outcome.winner, Some(Team::TWO));
+        assert_eq!(outcome.causes, [ScoreCause::Regular; 2]);
+        assert!(outcome.moves.is_empty());
+        for player in players {
+            let received = player.join().unwrap();
+            // No move is requested from red
+            assert!(!received.iter().any(|node| {
+                node.get_child("data")
+                    .and_then(|data| data.get_attribute("class"))
+                    == Some("moveRequest")
+            }));
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let config = ServerConfig {
+            move_time: Duration::from_millis(100),
+            hard_timeout: Duration::from_millis(300),
+        };
+        let server = Server::start("localhost:0", config).unwrap();
+        // The first player never answers
+        let (_one, _reader) = connect(&server, Request::Join { game_type: None });
+        let two = spawn_player(&server, Request::Join { game_type: None });
+
+        let outcome = server.next_result(TIMEOUT).unwrap();
+        assert_eq!(outcome.winner, Some(Team::TWO));
+        assert_eq!(outcome.causes[0], ScoreCause::HardTimeout);
+        assert!(outcome.moves.is_empty());
+        two.join().unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, ErrorKind};
+
+/// An element of an XML document with its attributes, text content and children.
+/// Just enough XML for the Software Challenge protocol, i.e. no namespaces, CDATA or doctypes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmlNode {
+    pub name: String,
+    pub attributes: BTreeMap<String, String>,
+    pub content: String,
+    pub children: Vec<XmlNode>,
+}
+
+impl XmlNode {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn attribute(mut self, key: &str, value: impl ToString) -> Self {
+        self.attributes.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn content(mut self, content: impl ToString) -> Self {
+        self.content = content.to_string();
+        self
+    }
+
+    pub fn child(mut self, child: XmlNode) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn children(mut self, children: impl IntoIterator<Item = XmlNode>) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    pub fn get_attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(String::as_str)
+    }
+
+    /// Returns: the first child with the given name
+    pub fn get_child(&self, name: &str) -> Option<&XmlNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+impl fmt::Display for XmlNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}", self.name)?;
+        for (key, value) in self.attributes.iter() {
+            write!(f, " {}=\"{}\"", key, escape(value))?;
+        }
+        if self.content.is_empty() && self.children.is_empty() {
+            return write!(f, "/>");
+        }
+        write!(f, ">{}", escape(&self.content))?;
+        for child in self.children.iter() {
+            write!(f, "{}", child)?;
+        }
+        write!(f, "</{}>", self.name)
+    }
+}
+
+/// The parts of an XML stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// An opening tag, which is also closed if it is self closing like `<join/>`
+    Start {
+        node: XmlNode,
+        self_closing: bool,
+    },
+    End(String),
+    Text(String),
+}
+
+/// Deepest nesting of elements the reader accepts, far more than the protocol needs,
+/// so a peer can't overflow the stack with nested elements.
+pub const MAX_DEPTH: usize = 64;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Reads XML incrementally from a stream, as the protocol never closes its root element
+/// until the end of the connection.
+pub struct XmlReader<R: BufRead> {
+    inner: R,
+}
+
+impl<R: BufRead> XmlReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+
+    fn read_until(&mut self, delimiter: u8) -> io::Result<String> {
+        let mut buf = vec![];
+        self.inner.read_until(delimiter, &mut buf)?;
+        if buf.last() != Some(&delimiter) {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Unexpected end of XML",
+            ));
+        }
+        buf.pop();
+        String::from_utf8(buf).map_err(|err| invalid(err.to_string()))
+    }
+
+    /// Returns: the next tag or non-empty text, or None at the end of the stream.
+    /// Declarations and comments are skipped.
+    pub fn next_token(&mut self) -> io::Result<Option<Token>> {
+        loop {
+            let peeked = self.inner.fill_buf()?;
+            match peeked.first() {
+                None => return Ok(None),
+                Some(b'<') => self.inner.consume(1),
+                Some(_) => {
+                    let mut buf = vec![];
+                    loop {
+                        let available = self.inner.fill_buf()?;
+                        if available.is_empty() {
+                            break;
+                        }
+                        match available.iter().position(|&b| b == b'<') {
+                            Some(end) => {
+                                buf.extend_from_slice(&available[..end]);
+                                self.inner.consume(end);
+                                break;
+                            }
+                            None => {
+                                let len = available.len();
+                                buf.extend_from_slice(available);
+                                self.inner.consume(len);
+                            }
+                        }
+                    }
+                    let text = String::from_utf8(buf).map_err(|err| invalid(err.to_string()))?;
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    return Ok(Some(Token::Text(unescape(text.trim()))));
+                }
+            }
+
+            let tag = self.read_until(b'>')?;
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                return Ok(Some(Token::End(name.trim().to_string())));
+            }
+            let (tag, self_closing) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag.as_str(), false),
+            };
+            return Ok(Some(Token::Start {
+                node: parse_tag(tag)?,
+                self_closing,
+            }));
+        }
+    }
+
+    /// Reads the next complete element inside of the root element, like the messages
+    /// inside of `<protocol>`.
+    /// Returns: None, if the root element was closed or the stream ended
+    pub fn next_element(&mut self) -> io::Result<Option<XmlNode>> {
+        loop {
+            match self.next_token()? {
+                Some(Token::Start {
+                    node,
+                    self_closing: true,
+                }) => return Ok(Some(node)),
+                Some(Token::Start { node, .. }) => return self.read_element(node).map(Some),
+                Some(Token::Text(_)) => {}
+                Some(Token::End(_)) | None => return Ok(None),
+            }
+        }
+    }
+
+    /// Reads the children and content of an element up to its closing tag.
+    pub fn read_element(&mut self, node: XmlNode) -> io::Result<XmlNode> {
+        self.read_nested(node, 1)
+    }
+
+    /// Reads an element at the given depth, where the element passed to
+    /// [`read_element`](Self::read_element) has a depth of 1.
+    fn read_nested(&mut self, mut node: XmlNode, depth: usize) -> io::Result<XmlNode> {
+        if depth > MAX_DEPTH {
+            return Err(invalid(format!(
+                "Elements nested deeper than {} in <{}>",
+                MAX_DEPTH, node.name
+            )));
+        }
+        loop {
+            match self.next_token()? {
+                Some(Token::Start {
+                    node: child,
+                    self_closing,
+                }) => {
+                    let child = if self_closing {
+                        child
+                    } else {
+                        self.read_nested(child, depth + 1)?
+                    };
+                    node.children.push(child);
+                }
+                Some(Token::End(name)) if name == node.name => return Ok(node),
+                Some(Token::End(name)) => {
+                    return Err(invalid(format!(
+                        "Expected </{}>, but found </{}>",
+                        node.name, name
+                    )))
+                }
+                Some(Token::Text(text)) => node.content.push_str(&text),
+                None => {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        format!("Unexpected end of XML in <{}>", node.name),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// Parses the inside of an opening tag like `data class="move"`.
+fn parse_tag(tag: &str) -> io::Result<XmlNode> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut node = XmlNode::new(&tag[..name_end]);
+    if node.name.is_empty() {
+        return Err(invalid(format!("Missing element name in <{}>", tag)));
+    }
+
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let bad = || invalid(format!("Invalid attributes in <{}>", tag));
+        let eq = rest.find('=').ok_or_else(bad)?;
+        let key = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let quote = quote.ok_or_else(bad)?;
+        let end = value[1..].find(quote).ok_or_else(bad)? + 1;
+        node.attributes
+            .insert(key.to_string(), unescape(&value[1..end]));
+        rest = value[end + 1..].trim_start();
+    }
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_write_and_read() {
+        let node = XmlNode::new("room").attribute("roomId", "a&b").child(
+            XmlNode::new("data")
+                .attribute("class", "move")
+                .child(XmlNode::new("from").attribute("x", 0).attribute("y", 7))
+                .child(XmlNode::new("text").content("<1 & 2>")),
+        );
+        let xml = node.to_string();
+        assert_eq!(
+            xml,
+            "<room roomId=\"a&amp;b\"><data class=\"move\"><from x=\"0\" y=\"7\"/>\
+             <text>&lt;1 &amp; 2&gt;</text></data></room>"
+        );
+
+        let mut reader = XmlReader::new(Cursor::new(format!(
+            "<?xml version=\"1.0\"?><protocol>\n  {}",
+            xml
+        )));
+        let start = match reader.next_token().unwrap() {
+            Some(Token::Start { node, self_closing }) => {
+                assert!(!self_closing);
+                node
+            }
+            token => panic!("Unexpected token {:?}", token),
+        };
+        assert_eq!(start.name, "protocol");
+        match reader.next_token().unwrap() {
+            Some(Token::Start { node: room, .. }) => {
+                assert_eq!(reader.read_element(room).unwrap(), node)
+            }
+            token => panic!("Unexpected token {:?}", token),
+        }
+        assert!(reader.next_token().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut reader = XmlReader::new(Cursor::new("<room><data></room>"));
+        let room = match reader.next_token().unwrap() {
+            Some(Token::Start { node, .. }) => node,
+            token => panic!("Unexpected token {:?}", token),
+        };
+        assert!(reader.read_element(room).is_err());
+
+        let nested = "<a>".repeat(MAX_DEPTH + 1) + &"</a>".repeat(MAX_DEPTH + 1);
+        let mut reader = XmlReader::new(Cursor::new(nested));
+        assert!(reader.next_element().is_err());
+        let nested = "<a>".repeat(MAX_DEPTH) + &"</a>".repeat(MAX_DEPTH);
+        let mut reader = XmlReader::new(Cursor::new(nested));
+        assert!(reader.next_element().unwrap().is_some());
+
+        let mut reader = XmlReader::new(Cursor::new("<join gameType=swc/>"));
+        assert!(reader.next_token().is_err());
+
+        let mut reader = XmlReader::new(Cursor::new("<join gameType=\"swc\""));
+        assert!(reader.next_token().is_err());
+    }
+}
@@ -0,0 +1,453 @@
+//! Plays games of our client against a scripted peer, which speaks the protocol of the
+//! game server over a local socket.
+
+use std::fs;
+use std::io::{BufReader, ErrorKind, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use client::algorithms::{Algorithm, RandomPick};
+use client::client::{Client, ClientOptions, SessionConfig};
+use client::game::{Gamestate, IGamestate, Move, Team};
+use client::join::JoinMode;
+use client::record::{GameRecord, RecordResult};
+use client::telemetry;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use server::protocol::{self, Request};
+use server::xml::{Token, XmlNode, XmlReader};
+use server::{GameOutcome, ScoreCause};
+
+const ROOM_ID: &str = "test-room";
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The server side of a connection to the client.
+struct Peer {
+    stream: TcpStream,
+    reader: XmlReader<BufReader<TcpStream>>,
+    start: Gamestate,
+    state: Gamestate,
+    moves: Vec<Move>,
+}
+
+impl Peer {
+    /// Accepts the client and lets it join any game, the client plays team ONE.
+    fn accept(listener: &TcpListener, start: Gamestate) -> Self {
+        let (peer, join) = Self::accept_request(listener, start);
+        assert!(matches!(join, Request::Join { .. }), "{:?}", join);
+        peer
+    }
+
+    /// Accepts the client and lets it join with whatever request it sends.
+    /// Returns: the peer and the client's join request
+    fn accept_request(listener: &TcpListener, start: Gamestate) -> (Self, Request) {
+        // Fail instead of hanging, if the client never connects
+        listener.set_nonblocking(true).unwrap();
+        let started = Instant::now();
+        let stream = loop {
+            match listener.accept() {
+                Ok((stream, _)) => break stream,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    assert!(started.elapsed() < TIMEOUT, "The client didn't connect");
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(err) => panic!("Failed to accept the client: {}", err),
+            }
+        };
+        stream.set_nonblocking(false).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        let mut reader = XmlReader::new(BufReader::new(stream.try_clone().unwrap()));
+        assert!(matches!(
+            reader.next_token().unwrap(),
+            Some(Token::Start { node, .. }) if node.name == "protocol"
+        ));
+        let join = Request::parse(reader.next_element().unwrap().unwrap());
+
+        let mut peer = Self {
+            stream,
+            reader,
+            start,
+            state: start,
+            moves: vec![],
+        };
+        write!(peer.stream, "<protocol>").unwrap();
+        peer.send(protocol::joined(ROOM_ID));
+        peer.send(protocol::welcome(ROOM_ID, Team::ONE));
+        (peer, join)
+    }
+
+    fn send(&mut self, node: XmlNode) {
+        write!(self.stream, "{}", node).unwrap();
+        self.stream.flush().unwrap();
+    }
+
+    fn send_memento(&mut self) {
+        self.send(protocol::memento(
+            ROOM_ID,
+            &self.state,
+            self.moves.last().copied(),
+        ));
+    }
+
+    /// Requests a move from the client and checks that it is legal.
+    fn request_move(&mut self) -> Move {
+        self.send(protocol::move_request(ROOM_ID));
+        let (from, to) = match Request::parse(self.reader.next_element().unwrap().unwrap()) {
+            Request::Move { room_id, from, to } => {
+                assert_eq!(room_id, ROOM_ID);
+                (from, to)
+            }
+            request => panic!("Expected a move, but got {:?}", request),
+        };
+        let mov = self
+            .state
+            .available_moves_current_player()
+            .iter()
+            .copied()
+            .find(|mov| mov.from == from && mov.to == to);
+        mov.unwrap_or_else(|| panic!("Illegal move from {} to {} in {}", from, to, self.state))
+    }
+
+    fn play(&mut self, mov: Move) {
+        self.state.apply_move(&mov);
+        self.moves.push(mov);
+    }
+
+    /// Plays the opponent's moves, until the game is over.
+    fn play_game(&mut self, duplicate_mementos: bool) {
+        loop {
+            self.send_memento();
+            if duplicate_mementos {
+                self.send_memento();
+            }
+            if self.state.game_over() {
+                return;
+            }
+            let mov = match self.state.current_player() {
+                Team::ONE => self.request_move(),
+                Team::TWO => self.state.available_moves_current_player()[0],
+            };
+            self.play(mov);
+        }
+    }
+
+    /// Sends the result of the game and closes the connection.
+    fn finish(&mut self, causes: [ScoreCause; 2]) -> GameOutcome {
+        let winner = match causes {
+            [ScoreCause::Regular, ScoreCause::Regular] => self.state.winner(),
+            [ScoreCause::Regular, _] => Some(Team::ONE),
+            _ => Some(Team::TWO),
+        };
+        let outcome = GameOutcome {
+            room_id: ROOM_ID.to_string(),
+            start: self.start,
+            moves: self.moves.clone(),
+            final_state: self.state,
+            winner,
+            causes,
+            reason: String::new(),
+        };
+        self.send(protocol::result(ROOM_ID, &outcome));
+        self.send(protocol::left(ROOM_ID));
+        write!(self.stream, "</protocol>").unwrap();
+        let _ = self.stream.shutdown(Shutdown::Write);
+        outcome
+    }
+}
+
+/// Takes its time for every move, so the game can end during its search.
+#[derive(Clone)]
+struct Slow;
+
+impl Algorithm for Slow {
+    fn best_move(&mut self, state: Gamestate, my_team: Team) -> Move {
+        thread::sleep(Duration::from_millis(300));
+        RandomPick.best_move(state, my_team)
+    }
+}
+
+/// Returns: a new, empty directory for game records
+fn record_dir() -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "ostseeschach-client-test-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::SeqCst)
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Starts a client with a random algorithm, which connects to the returned listener.
+/// Returns: the listener and a handle to the client, which finishes with whether
+/// `connect` returned successfully
+fn start_client(record_dir: Option<PathBuf>) -> (TcpListener, thread::JoinHandle<bool>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let client = thread::spawn(move || {
+        let mut client = Client::new(RandomPick, JoinMode::Any, record_dir);
+        client.connect("127.0.0.1", port).is_ok()
+    });
+    (listener, client)
+}
+
+/// Starts a client, which plays a session of games against the returned listener.
+/// Returns: the listener and a handle to the client, which finishes with the number of
+/// played games
+fn start_session(
+    record_dir: Option<PathBuf>,
+    games: usize,
+) -> (TcpListener, thread::JoinHandle<usize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let session = SessionConfig {
+        games,
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(10),
+        ..SessionConfig::default()
+    };
+    let client = thread::spawn(move || {
+        let mut client = Client::new(RandomPick, JoinMode::Any, record_dir);
+        client.play("127.0.0.1", port, &session).unwrap().len()
+    });
+    (listener, client)
+}
+
+fn read_records(dir: &Path) -> Vec<GameRecord> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+    files
+        .iter()
+        .map(|file| GameRecord::read_from(file).unwrap())
+        .collect()
+}
+
+fn read_record(dir: &Path) -> GameRecord {
+    let mut records = read_records(dir);
+    assert_eq!(records.len(), 1, "Expected exactly one record");
+    records.remove(0)
+}
+
+#[test]
+fn test_full_game() {
+    for seed in 0..3 {
+        let dir = record_dir();
+        let (listener, client) = start_client(Some(dir.clone()));
+        let start = Gamestate::new_random(&mut StdRng::seed_from_u64(seed));
+
+        let mut peer = Peer::accept(&listener, start);
+        peer.play_game(false);
+        let outcome = peer.finish([ScoreCause::Regular; 2]);
+        assert!(client.join().unwrap());
+
+        let record = read_record(&dir);
+        assert_eq!(record.start, start);
+        let moves: Vec<Move> = record.moves.iter().map(|recorded| recorded.mov).collect();
+        assert_eq!(moves, outcome.moves);
+        let expected = match outcome.winner {
+            Some(team) => RecordResult::Winner(team),
+            None => RecordResult::Draw,
+        };
+        assert_eq!(record.result, expected);
+        // Our own moves are recorded with the time they took
+        assert!(record
+            .moves
+            .iter()
+            .step_by(2)
+            .all(|recorded| recorded.time.is_some()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[test]
+fn test_duplicate_state_updates() {
+    let dir = record_dir();
+    let (listener, client) = start_client(Some(dir.clone()));
+    let start = Gamestate::new_random(&mut StdRng::seed_from_u64(42));
+
+    let mut peer = Peer::accept(&listener, start);
+    peer.play_game(true);
+    let outcome = peer.finish([ScoreCause::Regular; 2]);
+    assert!(client.join().unwrap());
+
+    let record = read_record(&dir);
+    let moves: Vec<Move> = record.moves.iter().map(|recorded| recorded.mov).collect();
+    assert_eq!(moves, outcome.moves);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_game_end_during_search() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let client = thread::spawn(move || {
+        let mut client = Client::new(Slow, JoinMode::Any, None);
+        client.connect("127.0.0.1", port).is_ok()
+    });
+    let start = Gamestate::new_random(&mut StdRng::seed_from_u64(7));
+
+    let mut peer = Peer::accept(&listener, start);
+    peer.send_memento();
+    // The game ends before the client could answer the move request
+    peer.send(protocol::move_request(ROOM_ID));
+    peer.finish([ScoreCause::HardTimeout, ScoreCause::Regular]);
+
+    // The client may still answer the request it got before the result, but nothing else
+    let mut sent = vec![];
+    while let Ok(Some(node)) = peer.reader.next_element() {
+        sent.push(Request::parse(node));
+    }
+    assert!(sent.len() <= 1, "Sent {:?} after the game ended", sent);
+    for request in sent {
+        match request {
+            Request::Move { room_id, from, to } => {
+                assert_eq!(room_id, ROOM_ID);
+                assert!(start
+                    .available_moves_current_player()
+                    .iter()
+                    .any(|mov| mov.from == from && mov.to == to));
+            }
+            request => panic!("Expected at most a move, but got {:?}", request),
+        }
+    }
+    // Whether the client notices the closed connection doesn't matter, as long as it doesn't panic
+    assert!(client.join().is_ok());
+}
+
+#[test]
+fn test_against_server() {
+    let server = server::Server::start("127.0.0.1:0", server::ServerConfig::default()).unwrap();
+    let port = server.local_addr().port();
+    let clients: Vec<_> = (0..2)
+        .map(|_| {
+            thread::spawn(move || {
+                let mut client = Client::new(RandomPick, JoinMode::Any, None);
+                client.connect("127.0.0.1", port).is_ok()
+            })
+        })
+        .collect();
+
+    let outcome = server.next_result(TIMEOUT).unwrap();
+    assert_eq!(outcome.causes, [ScoreCause::Regular; 2]);
+    assert!(outcome.final_state.game_over());
+    for client in clients {
+        assert!(client.join().unwrap());
+    }
+}
+
+#[test]
+fn test_consecutive_games() {
+    let dir = record_dir();
+    let (listener, client) = start_session(Some(dir.clone()), 2);
+
+    let mut outcomes = vec![];
+    for seed in 0..2 {
+        let start = Gamestate::new_random(&mut StdRng::seed_from_u64(seed));
+        let mut peer = Peer::accept(&listener, start);
+        peer.play_game(false);
+        outcomes.push(peer.finish([ScoreCause::Regular; 2]));
+    }
+    assert_eq!(client.join().unwrap(), 2);
+
+    let records = read_records(&dir);
+    assert_eq!(records.len(), 2);
+    for outcome in outcomes {
+        assert!(records.iter().any(|record| record.start == outcome.start
+            && record
+                .moves
+                .iter()
+                .map(|recorded| recorded.mov)
+                .eq(outcome.moves.iter().copied())));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_telemetry() {
+    let dir = record_dir();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let options = ClientOptions {
+        telemetry_dir: Some(dir.clone()),
+        ..ClientOptions::default()
+    };
+    let session = SessionConfig {
+        games: 2,
+        ..SessionConfig::default()
+    };
+    let client = thread::spawn(move || {
+        let mut client = Client::with_options(RandomPick, options);
+        client.play("127.0.0.1", port, &session).unwrap().len()
+    });
+
+    for seed in 0..2 {
+        let start = Gamestate::new_random(&mut StdRng::seed_from_u64(seed));
+        let mut peer = Peer::accept(&listener, start);
+        peer.play_game(false);
+        peer.finish([ScoreCause::Regular; 2]);
+    }
+    assert_eq!(client.join().unwrap(), 2);
+
+    // One file per game with a record for each of our moves
+    let files: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 2);
+    for file in files {
+        let records = telemetry::read_telemetry(&file).unwrap();
+        assert!(!records.is_empty());
+        assert!(records
+            .windows(2)
+            .all(|pair| pair[1].turn == pair[0].turn + 2));
+        assert!(records.iter().all(|record| record.legal_moves > 0));
+        assert!(records[1..]
+            .iter()
+            .all(|record| record.opponent_time_ms.is_some()));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_reconnect() {
+    let (listener, client) = start_session(None, 1);
+    let start = Gamestate::new_random(&mut StdRng::seed_from_u64(5));
+
+    // The connection breaks down during the game
+    let mut peer = Peer::accept(&listener, start);
+    peer.send_memento();
+    drop(peer);
+
+    let mut peer = Peer::accept(&listener, start);
+    peer.play_game(false);
+    peer.finish([ScoreCause::Regular; 2]);
+    assert_eq!(client.join().unwrap(), 1);
+}
+
+#[test]
+fn test_join_room() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let client = thread::spawn(move || {
+        let join = JoinMode::Room("open-room".to_string());
+        let mut client = Client::new(RandomPick, join, None);
+        client.connect("127.0.0.1", port).is_ok()
+    });
+    let start = Gamestate::new_random(&mut StdRng::seed_from_u64(11));
+
+    let (mut peer, join) = Peer::accept_request(&listener, start);
+    match join {
+        Request::JoinRoom { room_id } => assert_eq!(room_id, "open-room"),
+        request => panic!("Expected to join the room, but got {:?}", request),
+    }
+    peer.play_game(false);
+    peer.finish([ScoreCause::Regular; 2]);
+    assert!(client.join().unwrap());
+}